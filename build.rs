@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
@@ -25,14 +26,14 @@ fn main() {
         let room = value["room_number"].as_u64().unwrap();
         let track = value["track_number"].as_u64().unwrap();
         let item_id = value["default_item"].as_u64().unwrap();
-        let x_coord = value["xCoord"].as_u64().unwrap();
-        let y_coord = value["yCoord"].as_u64().unwrap();
-        let z_coord = value["zCoord"].as_u64().unwrap();
+        let x_coord = value["xCoord"].as_i64().unwrap();
+        let y_coord = value["yCoord"].as_i64().unwrap();
+        let z_coord = value["zCoord"].as_i64().unwrap();
         output.push_str(&format!(
             r#"        ("{}", ItemEntry {{ mission: {}, room_number: {}, track_number: {}, item_id: {}, coordinates: "#,
             key, mission_number, room, track, item_id
         ));
-        if x_coord != 0 {
+        if x_coord != 0 || y_coord != 0 || z_coord != 0 {
             output.push_str(&format!(
                 "Coordinates {{ x: {}, y: {}, z: {} }}",
                 x_coord, y_coord, z_coord
@@ -48,8 +49,81 @@ fn main() {
     });\n\n",
     );
 
+    // Shop purchases, so the store knows when it has run out of checks
+    let count_purchases = |prefix: &str| {
+        data.as_object()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .count()
+    };
+    output.push_str(&format!(
+        "pub const BLUE_ORB_PURCHASES: u8 = {};\n",
+        count_purchases("Purchase Blue Orb #")
+    ));
+    output.push_str(&format!(
+        "pub const PURPLE_ORB_PURCHASES: u8 = {};\n",
+        count_purchases("Purchase Purple Orb #")
+    ));
+
+    validate_coordinates(data.as_object().unwrap());
+
     // Write to src folder
     let out_dir = Path::new("src");
     let dest_path = Path::new(&out_dir).join("data/generated_locations.rs");
     fs::write(dest_path, output).expect("Unable to write generated_locations");
 }
+
+/// (mission, room, track, item)
+type PickupKind = (u64, u64, u64, u64);
+type Coords = (i64, i64, i64);
+
+/// Pickups of the same item in the same room (and mission) are told apart by their coordinates, so
+/// every one of them needs its own. Coordinates come from the debug log the client writes on pickup
+fn validate_coordinates(data: &serde_json::Map<String, serde_json::Value>) {
+    let mut groups: HashMap<PickupKind, Vec<(&String, Coords)>> = HashMap::new();
+    for (key, value) in data {
+        let track = value["track_number"].as_u64().unwrap();
+        if track == 0 {
+            // Not a pickup
+            continue;
+        }
+        groups
+            .entry((
+                value["mission_number"].as_u64().unwrap(),
+                value["room_number"].as_u64().unwrap(),
+                track,
+                value["default_item"].as_u64().unwrap(),
+            ))
+            .or_default()
+            .push((
+                key,
+                (
+                    value["xCoord"].as_i64().unwrap(),
+                    value["yCoord"].as_i64().unwrap(),
+                    value["zCoord"].as_i64().unwrap(),
+                ),
+            ));
+    }
+    let mut missing: Vec<&String> = vec![];
+    for entries in groups.values().filter(|entries| entries.len() > 1) {
+        for (i, (key, coords)) in entries.iter().enumerate() {
+            if *coords == (0, 0, 0) {
+                missing.push(key);
+            } else if let Some((other, _)) = entries[i + 1..].iter().find(|(_, c)| c == coords) {
+                panic!("{} and {} have the same coordinates", key, other);
+            }
+        }
+    }
+    if !missing.is_empty() {
+        missing.sort();
+        panic!(
+            "Pickups sharing a room with another of the same item need coordinates: {}",
+            missing
+                .iter()
+                .map(|key| key.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+}
@@ -1,10 +1,16 @@
 use crate::check_handler::{Location, TX_LOCATION};
+use crate::config::ServerDetails;
 use crate::constants::*;
-use crate::game_manager::{ARCHIPELAGO_DATA, ArchipelagoData, get_mission, with_session};
-use crate::mapping::{DeathlinkSetting, Goal, MAPPING, Mapping, OVERLAY_INFO, OverlayInfo};
-use crate::ui::overlay;
+use crate::game_manager::{ARCHIPELAGO_DATA, ArchipelagoData};
+use crate::game_memory::{DMC1Memory, GameMemory};
+use crate::mapping::{DeathlinkSetting, HintScouts, MAPPING, Mapping, OVERLAY_INFO, OverlayInfo};
+use crate::reconnect::ReconnectManager;
 use crate::ui::overlay::{MessageSegment, MessageType, OverlayMessage};
-use crate::{game_manager, hook, location_handler, mapping, skill_manager, utilities};
+use crate::ui::{chat_log, overlay};
+use crate::{
+    config, data_storage, game_manager, goal, hook, item_handler, location_handler, mapping,
+    save_handler, skill_manager,
+};
 use archipelago_rs::{
     AsItemId, Client, ClientStatus, Connection, ConnectionOptions, ConnectionState, CreateAsHint,
     DeathLinkOptions, Event, ItemHandling,
@@ -13,174 +19,337 @@ use randomizer_utilities::archipelago_utilities::{DeathLinkData, handle_print};
 use randomizer_utilities::item_sync::CURRENT_INDEX;
 use randomizer_utilities::ui::font_handler::{WHITE, YELLOW};
 use randomizer_utilities::{archipelago_utilities, item_sync, setup_channel_pair};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::sync::OnceLock;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub(crate) static CONNECTED: AtomicBool = AtomicBool::new(false);
 pub static TX_DEATHLINK: OnceLock<Sender<DeathLinkData>> = OnceLock::new();
 
-pub struct ArchipelagoCore {
-    pub connection: Connection<Mapping>,
+pub struct ArchipelagoCore<M: GameMemory = DMC1Memory> {
+    /// None until there's something to connect to, or after the player disconnects
+    connection: Option<Connection<Mapping>>,
+    memory: M,
+    /// False when driven from outside the game, nothing gets hooked or written to disk then
+    in_game: bool,
+    server: ServerDetails,
+    game_name: String,
     hooks_installed: bool,
     hooks_enabled: bool,
+    reconnect: ReconnectManager,
+    /// Lookups from the last connection, so checks made while it's down can still be saved
+    offline_lookup: Option<OfflineLookup>,
+    /// Checks made while the connection was down that couldn't be resolved, sent once it's back
+    pending_locations: VecDeque<Location>,
 
     location_receiver: Receiver<Location>,
     deathlink_receiver: Receiver<DeathLinkData>,
 }
 
 impl ArchipelagoCore {
-    pub fn new(url: String, game_name: String) -> anyhow::Result<Self> {
+    pub fn new(server: ServerDetails, game_name: String, connect: bool) -> anyhow::Result<Self> {
+        Self::with_memory(DMC1Memory, true, server, game_name, connect)
+    }
+}
+
+impl<M: GameMemory> ArchipelagoCore<M> {
+    pub fn with_memory(
+        memory: M,
+        in_game: bool,
+        server: ServerDetails,
+        game_name: String,
+        connect: bool,
+    ) -> anyhow::Result<Self> {
         Ok(Self {
-            connection: Connection::new(
-                url,
-                game_name,
-                "",
-                ConnectionOptions::new().receive_items(ItemHandling::OtherWorlds {
-                    own_world: true,
-                    starting_inventory: true,
-                }),
-            ),
+            connection: connect.then(|| create_connection(&server, game_name.clone())),
+            memory,
+            in_game,
+            server,
+            game_name,
             hooks_installed: false,
             hooks_enabled: false,
+            reconnect: ReconnectManager::new(config::CONFIG.connections.reconnect_interval_seconds),
+            offline_lookup: None,
+            pending_locations: VecDeque::new(),
             location_receiver: setup_channel_pair(&TX_LOCATION),
             deathlink_receiver: setup_channel_pair(&TX_DEATHLINK),
         })
     }
 
+    pub fn client(&self) -> Option<&Client<Mapping>> {
+        self.connection.as_ref().and_then(Connection::client)
+    }
+
+    pub fn client_mut(&mut self) -> Option<&mut Client<Mapping>> {
+        self.connection.as_mut().and_then(Connection::client_mut)
+    }
+
+    /// Drop whatever connection there is and start a new one to the given room
+    pub fn connect(&mut self, server: ServerDetails) {
+        log::info!("Connecting to {}", server.url);
+        CONNECTED.store(false, Ordering::SeqCst);
+        self.reconnect.on_connected();
+        if let Ok(mut info) = OVERLAY_INFO.write() {
+            info.reconnect_at = None;
+        }
+        self.connection = Some(create_connection(&server, self.game_name.clone()));
+        self.server = server;
+    }
+
+    /// Ask the server for every received item again. Goes over the current connection, reconnecting
+    /// (which also sends them all) is only for when there isn't a usable one
+    pub fn resync(&mut self) {
+        log::info!("Requesting received items from the server again");
+        if let Some(client) = self.client_mut() {
+            match client.sync() {
+                Ok(()) => return,
+                Err(err) => log::error!("Failed to send Sync, reconnecting instead: {}", err),
+            }
+        }
+        self.connect(self.server.clone());
+    }
+
+    /// Close the connection and put the game back to normal, no reconnection attempts are made
+    pub fn disconnect(&mut self) {
+        self.connection = None;
+        CONNECTED.store(false, Ordering::SeqCst);
+        self.reconnect.on_connected();
+        if let Ok(mut info) = OVERLAY_INFO.write() {
+            *info = OverlayInfo::default();
+        }
+        restore_game(&mut self.hooks_enabled);
+    }
+
     pub fn update(&mut self) -> Result<(), Box<dyn Error>> {
-        for event in self.connection.update() {
-            match event {
-                Event::Connected => {
-                    log::info!("Connected!");
-                    log::debug!("Mod version: {}", env!("CARGO_PKG_VERSION"));
-                    let mapping = self.connection.client().unwrap().slot_data();
-                    let mut overlay_info = OVERLAY_INFO.write()?;
-                    log::info!("Running in randomizer mode");
-                    overlay_info.generated_version = mapping.generated_version;
-                    overlay_info.client_version = mapping.client_version;
-                    MAPPING.write()?.replace(mapping.clone());
-                    item_sync::send_offline_checks(self.connection.client_mut().unwrap())?;
-                    if !self.hooks_installed {
-                        // Hooks needed to modify the game
-                        unsafe {
-                            match hook::create_hooks() {
-                                Ok(_) => {
-                                    log::debug!("Created DMC1 Hooks");
-                                    self.hooks_installed = true;
-                                }
-                                Err(err) => {
-                                    log::error!("Failed to create hooks: {:?}", err);
-                                }
-                            }
-                        }
+        let Some(connection) = self.connection.as_mut() else {
+            return self.handle_channels();
+        };
+        for event in connection.update() {
+            self.handle_event(event)?;
+        }
+        match self.connection.as_ref().map(Connection::state) {
+            None | Some(ConnectionState::Connecting(_)) => {}
+            Some(ConnectionState::Connected(_)) => {
+                CONNECTED.store(true, Ordering::SeqCst);
+            }
+            Some(ConnectionState::Disconnected(state)) => {
+                CONNECTED.store(false, Ordering::SeqCst);
+                let now = Instant::now();
+                if !self.reconnect.is_waiting() {
+                    // Either the connection just dropped or the last attempt failed
+                    let delay = self.reconnect.on_failure(now);
+                    log::error!(
+                        "Disconnected from server: {:?}, reconnecting in {}s",
+                        state,
+                        delay.as_secs()
+                    );
+                    OVERLAY_INFO.write()?.reconnect_at = self.reconnect.next_attempt();
+                } else {
+                    if self.reconnect.grace_period_expired(now) && MAPPING.read()?.is_some() {
+                        log::warn!("Server has been unreachable for too long");
+                        *OVERLAY_INFO.write()? = OverlayInfo {
+                            reconnect_at: self.reconnect.next_attempt(),
+                            ..Default::default()
+                        };
+                        restore_game(&mut self.hooks_enabled);
                     }
-                    if self.hooks_installed && !self.hooks_enabled {
-                        hook::enable_hooks();
-                        self.hooks_enabled = true;
+                    if self.reconnect.should_attempt(now) {
+                        log::info!(
+                            "Attempting to reconnect (attempt {})",
+                            self.reconnect.attempts()
+                        );
+                        OVERLAY_INFO.write()?.reconnect_at = None;
+                        self.connection =
+                            Some(create_connection(&self.server, self.game_name.clone()));
                     }
-                    run_setup(self.connection.client_mut().unwrap())?;
-
-                    // Print out version info
-                    log::debug!(
-                        "Client version: {}",
-                        if let Some(cv) = overlay_info.client_version {
-                            cv.to_string()
-                        } else {
-                            "Unknown".to_string()
-                        }
-                    );
+                }
+            }
+        }
+        self.handle_channels()?;
+        Ok(())
+    }
 
-                    log::debug!(
-                        "Generated version: {}",
-                        if let Some(gv) = overlay_info.generated_version {
-                            gv.to_string()
-                        } else {
-                            "Unknown".to_string()
+    /// Handles a single event from the server
+    pub fn handle_event(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
+        match event {
+            Event::Connected => {
+                log::info!("Connected!");
+                self.reconnect.on_connected();
+                log::debug!("Mod version: {}", env!("CARGO_PKG_VERSION"));
+                let mapping = self.client().unwrap().slot_data();
+                let mut overlay_info = OVERLAY_INFO.write()?;
+                log::info!("Running in randomizer mode");
+                overlay_info.generated_version = mapping.generated_version;
+                overlay_info.client_version = mapping.client_version;
+                MAPPING.write()?.replace(mapping.clone());
+                item_sync::send_offline_checks(self.client_mut().unwrap())?;
+                let client = self.client_mut().unwrap();
+                if has_reached_goal(client) {
+                    client.set_status(ClientStatus::Goal)?;
+                }
+                if self.in_game
+                    && let Err(err) = config::save_server_details(&self.server)
+                {
+                    log::error!("Failed to save connection details: {}", err);
+                }
+                if self.in_game && !self.hooks_installed {
+                    // Hooks needed to modify the game
+                    unsafe {
+                        match hook::create_hooks() {
+                            Ok(_) => {
+                                log::debug!("Created DMC1 Hooks");
+                                self.hooks_installed = true;
+                            }
+                            Err(err) => {
+                                log::error!("Failed to create hooks: {:?}", err);
+                            }
                         }
-                    );
+                    }
                 }
-                Event::Updated(_) => {}
-                Event::Print(print) => {
-                    let str = handle_print(print);
-                    log::info!("Print from server: {}", str);
+                if self.hooks_installed && !self.hooks_enabled {
+                    hook::enable_hooks();
+                    self.hooks_enabled = true;
                 }
-                Event::ReceivedItems(idx) => {
-                    handle_received_items_packet(idx, self.connection.client_mut().unwrap())?;
+                run_setup(self.client_mut().unwrap())?;
+                self.offline_lookup = self.client().map(OfflineLookup::new);
+
+                // Print out version info
+                log::debug!(
+                    "Client version: {}",
+                    if let Some(cv) = overlay_info.client_version {
+                        cv.to_string()
+                    } else {
+                        "Unknown".to_string()
+                    }
+                );
+
+                log::debug!(
+                    "Generated version: {}",
+                    if let Some(gv) = overlay_info.generated_version {
+                        gv.to_string()
+                    } else {
+                        "Unknown".to_string()
+                    }
+                );
+            }
+            Event::Updated(_) => {}
+            Event::Print(print) => {
+                chat_log::add_print(&print);
+                let str = handle_print(print);
+                log::info!("Print from server: {}", str);
+            }
+            Event::ReceivedItems(idx) => {
+                let client = self.connection.as_mut().and_then(Connection::client_mut);
+                let client = client.unwrap();
+                handle_received_items_packet(&self.memory, idx, client)?;
+                save_handler::finish_resync(&self.memory, client)?;
+            }
+            Event::Error(err) => log::error!("{}", err),
+            Event::Bounce {
+                games: _,
+                slots: _,
+                tags,
+                data: _,
+            } => {
+                // Progress is shared through data storage, nothing is sent over bounces. Death links arrive as their own event
+                log::debug!("Ignoring bounce with tags {:?}", tags);
+            }
+            Event::DeathLink {
+                games: _,
+                slots: _,
+                tags: _,
+                time: _,
+                cause,
+                source,
+            } => {
+                // The server bounces our own deaths back to us, Dante is already dead for those
+                if source == self.client().unwrap().this_player().name() {
+                    return Ok(());
                 }
-                Event::Error(err) => log::error!("{}", err),
-                Event::Bounce {
-                    games: _,
-                    slots: _,
-                    tags: _,
-                    data: _,
-                } => {}
-                Event::DeathLink {
-                    games: _,
-                    slots: _,
-                    tags: _,
-                    time: _,
-                    cause,
-                    source,
-                } => {
-                    overlay::add_message(OverlayMessage::new(
-                        vec![MessageSegment::new(
-                            format!("{}: {}", source, cause.unwrap_or_default()),
-                            WHITE,
-                        )],
-                        Duration::from_secs(3),
-                        // TODO May want to adjust position, currently added to the 'notification list' so it's in the upper right queue
-                        0.0,
-                        0.0,
-                        MessageType::Notification,
-                    ));
+                overlay::add_message(OverlayMessage::new(
+                    vec![MessageSegment::new(
+                        format!("{}: {}", source, cause.unwrap_or_default()),
+                        WHITE,
+                    )],
+                    Duration::from_secs(3),
+                    // TODO May want to adjust position, currently added to the 'notification list' so it's in the upper right queue
+                    0.0,
+                    0.0,
+                    MessageType::Notification,
+                ));
 
-                    match self.connection.client().unwrap().slot_data().death_link {
-                        DeathlinkSetting::DeathLink => {
-                            game_manager::kill_dante();
-                        }
-                        DeathlinkSetting::HurtLink => {
-                            game_manager::hurt_dante();
-                        }
-                        DeathlinkSetting::Off => {}
+                match self.client().unwrap().slot_data().death_link {
+                    DeathlinkSetting::DeathLink => {
+                        game_manager::kill_dante(&self.memory);
+                    }
+                    DeathlinkSetting::HurtLink => {
+                        game_manager::hurt_dante(&self.memory, &config::CONFIG.hurt_link);
                     }
+                    DeathlinkSetting::Off => {}
+                }
+            }
+            Event::KeyChanged {
+                key,
+                old_value: _,
+                new_value,
+                player: _,
+            } => {
+                if let Some(client) = self.client() {
+                    data_storage::handle_key_changed(client, &key, new_value);
                 }
-                Event::KeyChanged {
-                    key: _,
-                    old_value: _,
-                    new_value: _,
-                    player: _,
-                } => {}
             }
         }
-        match self.connection.state() {
-            ConnectionState::Connecting(_) => {}
-            ConnectionState::Connected(_) => {
-                CONNECTED.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Keep a check made while disconnected. If it can be resolved it goes in with the offline checks, so it's
+    /// saved with the game and sent on reconnecting, otherwise it waits in memory for the connection
+    fn hold_location(&mut self, location: Location) {
+        match self
+            .offline_lookup
+            .as_mut()
+            .map(|lookup| lookup.resolve(&location))
+        {
+            Some(Ok(id)) => {
+                log::info!(
+                    "Not connected, saving location check for later: {}",
+                    location
+                );
+                item_sync::add_offline_check(id);
             }
-            ConnectionState::Disconnected(state) => {
-                CONNECTED.store(false, Ordering::SeqCst);
-                *OVERLAY_INFO.write()? = OverlayInfo::default();
-                disconnect(&mut self.hooks_enabled);
-                return Err(format!("Disconnected from server: {:?}", state).into());
+            Some(Err(err)) => {
+                log::warn!("Unable to resolve offline check {}: {}", location, err);
+                self.pending_locations.push_back(location);
+            }
+            None => {
+                log::info!("Not connected, holding on to location check: {}", location);
+                self.pending_locations.push_back(location);
             }
         }
-        self.handle_channels()?;
-        Ok(())
     }
 
     pub fn handle_channels(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(client) = self.client_mut() {
+            data_storage::sync_progress(client);
+            data_storage::update_hints(client);
+        }
+
+        // Catch up on anything checked while offline, one per frame
+        if let Some(client) = self.connection.as_mut().and_then(Connection::client_mut)
+            && let Some(location) = self.pending_locations.pop_front()
+        {
+            handle_item_receive(client, location)?;
+        }
+
         match self.location_receiver.try_recv() {
             Ok(location) => {
-                if let Some(client) = self.connection.client_mut() {
+                if let Some(client) = self.client_mut() {
                     handle_item_receive(client, location)?;
                 } else {
-                    log::error!(
-                        "Received location check while client was None: {}",
-                        location
-                    );
+                    self.hold_location(location);
                 }
             }
             Err(err) => {
@@ -191,11 +360,13 @@ impl ArchipelagoCore {
         }
 
         match self.deathlink_receiver.try_recv() {
-            Ok(dl_data) => self
-                .connection
-                .client_mut()
-                .unwrap()
-                .death_link(DeathLinkOptions::new().cause(dl_data.cause))?,
+            Ok(dl_data) => {
+                if let Some(client) = self.client_mut() {
+                    client.death_link(DeathLinkOptions::new().cause(dl_data.cause))?
+                } else {
+                    log::debug!("Not connected, dropping DeathLink");
+                }
+            }
             Err(err) => {
                 if err == TryRecvError::Disconnected {
                     return Err("Disconnected from DeathLink receiver".into());
@@ -206,19 +377,37 @@ impl ArchipelagoCore {
     }
 }
 
-pub(crate) fn handle_received_items_packet(
+/// Sets up a new connection to the given server (a local client, a MultiServer or a stand-in for one)
+pub fn create_connection(server: &ServerDetails, game_name: String) -> Connection<Mapping> {
+    let mut options = ConnectionOptions::new().receive_items(ItemHandling::OtherWorlds {
+        own_world: true,
+        starting_inventory: true,
+    });
+    if !server.password.is_empty() {
+        options = options.password(server.password.clone());
+    }
+    Connection::new(
+        server.url.clone(),
+        game_name,
+        server.slot_name.clone(),
+        options,
+    )
+}
+
+pub(crate) fn handle_received_items_packet<M: GameMemory>(
+    memory: &M,
     index: usize,
     client: &mut Client<Mapping>,
 ) -> Result<(), Box<dyn Error>> {
     if index == 0 {
-        *ARCHIPELAGO_DATA.write()? = ArchipelagoData::default();
+        reset_item_data(memory, client)?;
     }
 
     match ARCHIPELAGO_DATA.write() {
         Ok(mut data) => {
             for item in client.received_items().iter() {
                 // Display overlay text if we're not at the main menu
-                if !utilities::is_on_main_menu()
+                if !memory.on_main_menu()
                     && item.index() >= CURRENT_INDEX.load(Ordering::SeqCst) as usize
                 {
                     let rec_msg: Vec<MessageSegment> = vec![
@@ -239,98 +428,14 @@ pub(crate) fn handle_received_items_packet(
                     ));
                 }
 
-                match item.item().as_item_id() {
-                    41..=43 => {
-                        if item.index() >= CURRENT_INDEX.load(Ordering::SeqCst) as usize {
-                            let orbs = match item.item().as_item_id() {
-                                41 => 100,
-                                42 => 150,
-                                43 => 200,
-                                _ => unreachable!(),
-                            };
-                            game_manager::give_red_orbs(orbs);
-                        }
-                    }
-                    1..=5 => {
-                        // Guns
-                        utilities::insert_unique_item_into_inv(
-                            ITEM_DATA_MAP.get(&&*item.item().name()).unwrap(),
-                        )
-                    }
-                    6 => {
-                        data.add_blue_orb();
-                        //ADD_ORB_FUNC(0);
-                        game_manager::give_hp(1);
-                    }
-                    7 => {
-                        data.add_purple_orb();
-                        //ADD_ORB_FUNC(1);
-                        game_manager::give_magic(1, &data);
-                    }
-                    8..=11 => {
-                        // Weapons
-                        utilities::insert_unique_item_into_inv(
-                            ITEM_DATA_MAP.get(&&*item.item().name()).unwrap(),
-                        )
-                    }
-                    12..=16 => {
-                        // Don't add duplicate consumables
-                        if item.index() >= CURRENT_INDEX.load(Ordering::SeqCst) as usize {
-                            if item.item().id() == 15 {
-                                with_session(|session| {
-                                    session.yellow_orbs += 1;
-                                })
-                                .unwrap();
-                            } else {
-                                utilities::insert_item_into_inv(
-                                    ITEM_DATA_MAP.get(&item.item().name().as_str()).unwrap(),
-                                )
-                            }
-                        }
-                    }
-                    39 => {
-                        // DT Unlock
-                        data.add_dt();
-                        // for _ in 0..3 {
-                        //     ADD_ORB_FUNC(1);
-                        // }
-                        game_manager::give_magic(3, &data);
-                    }
-                    18..=38 => {
-                        // For key items
-                        log::debug!("Setting newly acquired key items");
-                        match MISSION_ITEM_MAP.get(&(get_mission())) {
-                            None => {} // No items for the mission
-                            Some(item_list) => {
-                                if item_list.contains(&&*item.item().name()) {
-                                    utilities::insert_unique_item_into_inv(
-                                        ITEM_DATA_MAP.get(&&*item.item().name()).unwrap(),
-                                    )
-                                }
-                            }
-                        }
-                    }
-                    17 => {
-                        log::debug!("Giving Bangle of time");
-                        utilities::insert_unique_item_into_inv(
-                            ITEM_DATA_MAP.get(&&*item.item().name()).unwrap(),
-                        )
-                    }
-                    100..=113 => {
-                        // For skills
-                        if client.slot_data().randomize_skills {
-                            skill_manager::add_skill(item.item().id() as usize, &mut data);
-                            skill_manager::set_skills(&data); // Hacky...
-                        }
-                    }
-                    _ => {
-                        log::warn!(
-                            "Unhandled item ID: {} ({})",
-                            item.item().name(),
-                            item.item().id()
-                        )
-                    }
-                }
+                item_handler::give_item(
+                    memory,
+                    &mut data,
+                    item.item().id(),
+                    &item.item().name(),
+                    item.index() >= CURRENT_INDEX.load(Ordering::SeqCst) as usize,
+                    client.slot_data().randomize_skills,
+                );
                 data.add_item(item.item().name().into());
                 if item.index() >= CURRENT_INDEX.load(Ordering::SeqCst) as usize {
                     CURRENT_INDEX.store((item.index() + 1) as i64, Ordering::SeqCst);
@@ -345,6 +450,115 @@ pub(crate) fn handle_received_items_packet(
     Ok(())
 }
 
+/// Clears out the tracked items, leaving only what the slot starts with
+pub(crate) fn reset_item_data<M: GameMemory>(
+    memory: &M,
+    client: &Client<Mapping>,
+) -> Result<(), Box<dyn Error>> {
+    let mut data = ARCHIPELAGO_DATA.write()?;
+    *data = ArchipelagoData::default();
+    give_starter_items(memory, &mut data, client, false);
+    Ok(())
+}
+
+/// Gives the slot's starter items the same way received items are given
+pub(crate) fn give_starter_items<M: GameMemory>(
+    memory: &M,
+    data: &mut ArchipelagoData,
+    client: &Client<Mapping>,
+    is_new: bool,
+) {
+    let mapping = client.slot_data();
+    let from_server: Vec<String> = client
+        .received_items()
+        .iter()
+        // Slot 0 is the server itself, that's where the starting inventory comes from
+        .filter(|item| item.sender().slot() == 0)
+        .map(|item| item.item().name().to_string())
+        .collect();
+    for item_name in starter_items_to_give(&mapping.starter_items, &from_server) {
+        match client.this_game().item_by_name(item_name) {
+            Some(item) => {
+                item_handler::give_item(
+                    memory,
+                    data,
+                    item.id(),
+                    item_name,
+                    is_new,
+                    mapping.randomize_skills,
+                );
+                data.add_item(item_name.clone());
+            }
+            None => log::warn!("Unknown starter item: {}", item_name),
+        }
+    }
+}
+
+/// The starter items that still need giving. Any that are in the slot's starting inventory have been sent by the
+/// server already and are given like any other received item, so one copy is skipped for each of those.
+fn starter_items_to_give<'a>(
+    starter_items: &'a [String],
+    from_server: &[String],
+) -> Vec<&'a String> {
+    let mut already_sent: HashMap<&str, usize> = HashMap::new();
+    for name in from_server {
+        *already_sent.entry(name.as_str()).or_default() += 1;
+    }
+    starter_items
+        .iter()
+        .filter(|name| match already_sent.get_mut(name.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                log::debug!("{} is in the starting inventory, not giving it again", name);
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// Enough of the data package to work out which location a check was while the server can't be reached
+struct OfflineLookup {
+    item_ids: HashMap<&'static str, i64>,
+    checked: HashSet<String>,
+}
+
+impl OfflineLookup {
+    fn new(client: &Client<Mapping>) -> Self {
+        Self {
+            item_ids: ITEM_DATA_MAP
+                .keys()
+                .filter_map(|name| {
+                    client
+                        .this_game()
+                        .item_by_name(name)
+                        .map(|item| (*name, item.id()))
+                })
+                .collect(),
+            checked: client
+                .checked_locations()
+                .map(|loc| loc.name().to_string())
+                .collect(),
+        }
+    }
+
+    /// The ID of the location that was checked, it counts as checked from then on
+    fn resolve(&mut self, location: &Location) -> Result<i64, Box<dyn Error>> {
+        let name = location_handler::resolve_location_name(
+            location,
+            |item| self.item_ids.get(item).copied(),
+            |key| self.checked.contains(key),
+        )?;
+        let id = archipelago_utilities::CACHED_LOCATIONS
+            .read()?
+            .get(name)
+            .map(|located_item| located_item.location().id())
+            .ok_or_else(|| format!("Location wasn't scouted: {}", name))?;
+        self.checked.insert(name.to_string());
+        Ok(id)
+    }
+}
+
 fn handle_item_receive(
     client: &mut Client<Mapping>,
     received_item: Location,
@@ -390,28 +604,12 @@ fn handle_item_receive(
     Ok(())
 }
 
-fn has_reached_goal(client: &mut Client<Mapping>) -> bool {
-    let mut chk = client.checked_locations();
-    match client.slot_data().goal {
-        Goal::Standard => chk.any(|loc| loc.name() == "Mission #20 Complete"),
-        Goal::All => {
-            for i in 1..20 {
-                // If we are missing a mission complete check then we cannot goal
-                if !chk.any(|loc| loc.name() == format!("Mission #{} Complete", i).as_str()) {
-                    return false;
-                }
-            }
-            // If we have them all, goal
-            true
-        }
-        Goal::RandomOrder => {
-            if let Some(order) = &client.slot_data().mission_order {
-                return chk
-                    .any(|loc| loc.name() == format!("Mission #{} Complete", order[19]).as_str());
-            }
-            false
-        }
-    }
+fn has_reached_goal(client: &Client<Mapping>) -> bool {
+    let checked: Vec<String> = client
+        .checked_locations()
+        .map(|loc| loc.name().to_string())
+        .collect();
+    goal::has_reached_goal(client.slot_data(), &checked)
 }
 
 const GENERIC_CHECKS: u32 = 40;
@@ -419,18 +617,32 @@ const GENERIC_CHECKS: u32 = 40;
 /// This is run when a there is a valid connection to a room.
 pub fn run_setup(client: &mut Client<Mapping>) -> Result<(), Box<dyn Error>> {
     log::info!("Running setup");
+    let hint_scouts = config::CONFIG
+        .hints
+        .scouts
+        .unwrap_or(client.slot_data().hint_scouts);
+    let hint_for = |mission: u32| match (hint_scouts, mission) {
+        (HintScouts::All, _) | (HintScouts::Shop, GENERIC_CHECKS) => CreateAsHint::New,
+        _ => CreateAsHint::No,
+    };
     // Shop checks
-    // TODO Hint options
-    mapping::run_scouts_for_mission(client, GENERIC_CHECKS, CreateAsHint::New);
-    mapping::run_scouts_for_mission(client, NO_MISSION, CreateAsHint::No);
+    mapping::run_scouts_for_mission(client, GENERIC_CHECKS, hint_for(GENERIC_CHECKS));
+    mapping::run_scouts_for_mission(client, NO_MISSION, hint_for(NO_MISSION));
     for i in 1..=23 {
-        mapping::run_scouts_for_mission(client, i, CreateAsHint::No);
+        mapping::run_scouts_for_mission(client, i, hint_for(i));
     }
-    mapping::run_scouts_for_secret_mission(client);
+    mapping::run_scouts_for_secret_mission(client, hint_for(NO_MISSION));
+    let checked: Vec<String> = client
+        .checked_locations()
+        .map(|loc| loc.name().to_string())
+        .collect();
+    skill_manager::set_purchased_skills(&checked);
+    data_storage::watch_hints(client);
+    data_storage::watch_progress(client);
     Ok(())
 }
 
-fn disconnect(hooks_enabled: &mut bool) {
+fn restore_game(hooks_enabled: &mut bool) {
     log::info!("Disconnecting and restoring game");
     if *hooks_enabled {
         match hook::disable_hooks() {
@@ -448,3 +660,217 @@ fn disconnect(hooks_enabled: &mut bool) {
     *ARCHIPELAGO_DATA.write().unwrap() = ArchipelagoData::default(); // Reset Data (Probably not needed)
     log::info!("Game restored to default state");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check_handler::LocationType;
+    use crate::constants::EMPTY_COORDINATES;
+    use crate::fake_server::FakeServer;
+    use crate::game_memory::MockMemory;
+    use randomizer_utilities::item_sync::OFFLINE_CHECKS;
+    use serde_json::json;
+    use std::thread;
+
+    const SLOT_NAME: &str = "Dante";
+
+    /// Keep updating the core, the same as the main loop would, until `done` says to stop
+    fn update_until<F>(core: &mut ArchipelagoCore<MockMemory>, waiting_for: &str, done: F)
+    where
+        F: Fn(&ArchipelagoCore<MockMemory>) -> bool,
+    {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done(core) {
+            assert!(
+                Instant::now() < deadline,
+                "Timed out waiting for {}",
+                waiting_for
+            );
+            core.update().unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn mission_complete(mission: u32) -> Location {
+        Location {
+            location_type: LocationType::MissionComplete,
+            item_id: 0,
+            room: 0,
+            track: 0,
+            mission,
+            coordinates: EMPTY_COORDINATES,
+            item_category: 0,
+        }
+    }
+
+    fn is_scouted(name: &str) -> bool {
+        archipelago_utilities::CACHED_LOCATIONS
+            .read()
+            .is_ok_and(|locations| locations.contains_key(name))
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn starter_items_skip_the_starting_inventory() {
+        let starter = names(&["Shotgun", "Vital Star S", "Vital Star S", "Grenadegun"]);
+        let from_server = names(&["Vital Star S", "Shotgun", "Blue Orb"]);
+        assert_eq!(
+            starter_items_to_give(&starter, &from_server),
+            vec!["Vital Star S", "Grenadegun"]
+        );
+    }
+
+    #[test]
+    fn starter_items_are_all_given_without_a_starting_inventory() {
+        let starter = names(&["Shotgun", "Grenadegun"]);
+        assert_eq!(
+            starter_items_to_give(&starter, &[]),
+            vec!["Shotgun", "Grenadegun"]
+        );
+    }
+
+    // Everything runs through one core since the location and DeathLink channels can only be set up once
+    #[test]
+    fn full_session_against_fake_server() {
+        let server = FakeServer::start(
+            SLOT_NAME,
+            json!({
+                "starter_items": ["Shotgun"],
+                "randomize_skills": false,
+                "purple_orb_mode": false,
+                "devil_trigger_mode": false,
+                "death_link": 1,
+                "goal": 0,
+                "mission_order": null,
+                "hint_scouts": 0,
+            }),
+        );
+        let details = ServerDetails {
+            url: server.url.clone(),
+            slot_name: SLOT_NAME.to_string(),
+            password: String::new(),
+        };
+        let mut core = ArchipelagoCore::with_memory(
+            MockMemory::new(),
+            false,
+            details,
+            GAME_NAME.to_string(),
+            true,
+        )
+        .unwrap();
+        core.reconnect = ReconnectManager::new(1);
+        core.memory.player.borrow_mut().hp = 500;
+
+        // Connect
+        update_until(&mut core, "the connection", |_| {
+            CONNECTED.load(Ordering::SeqCst) && MAPPING.read().unwrap().is_some()
+        });
+        assert!(!core.hooks_installed);
+        assert!(ARCHIPELAGO_DATA.read().unwrap().items.contains("Shotgun"));
+
+        // Receive items
+        server.send_items(vec![6, 42]);
+        update_until(&mut core, "received items", |_| {
+            ARCHIPELAGO_DATA.read().unwrap().blue_orbs == 1
+        });
+        assert_eq!(core.memory.session.borrow().red_orbs, 150);
+        assert_eq!(CURRENT_INDEX.load(Ordering::SeqCst), 2);
+
+        // Check a location
+        update_until(&mut core, "scouts", |_| is_scouted("Mission #1 Complete"));
+        let tx = TX_LOCATION.get().unwrap();
+        tx.send(mission_complete(1)).unwrap();
+        let mission_1 = server.location_id("Mission #1 Complete");
+        update_until(&mut core, "the location check", |_| {
+            server.log.lock().unwrap().checked_locations == vec![mission_1]
+        });
+
+        // Goal
+        tx.send(mission_complete(20)).unwrap();
+        update_until(&mut core, "the goal", |_| {
+            server.log.lock().unwrap().statuses.contains(&30)
+        });
+
+        // Our own DeathLink comes back from the server and shouldn't kill Dante
+        TX_DEATHLINK
+            .get()
+            .unwrap()
+            .send(DeathLinkData {
+                cause: "Dante fell".to_string(),
+            })
+            .unwrap();
+        update_until(&mut core, "the DeathLink to be sent", |_| {
+            server.log.lock().unwrap().death_links.len() == 1
+        });
+        for _ in 0..20 {
+            core.update().unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(core.memory.player.borrow().hp, 500);
+
+        server.death_link("Vergil", "Vergil fell");
+        update_until(&mut core, "the DeathLink", |core| {
+            core.memory.player.borrow().hp == 0
+        });
+
+        // Reconnect after the connection drops, without handing out the items again
+        server.disconnect();
+        update_until(&mut core, "the disconnect", |_| {
+            !CONNECTED.load(Ordering::SeqCst)
+        });
+
+        // Checked while offline, kept with the offline checks so it would go in the save
+        tx.send(mission_complete(2)).unwrap();
+        let mission_2 = server.location_id("Mission #2 Complete");
+        update_until(&mut core, "the offline check", |_| {
+            OFFLINE_CHECKS.lock().unwrap().contains(&mission_2)
+        });
+        assert!(!CONNECTED.load(Ordering::SeqCst));
+
+        update_until(&mut core, "the reconnection", |_| {
+            CONNECTED.load(Ordering::SeqCst) && server.log.lock().unwrap().connects == 2
+        });
+        update_until(&mut core, "the offline check to be sent", |_| {
+            server
+                .log
+                .lock()
+                .unwrap()
+                .checked_locations
+                .contains(&mission_2)
+        });
+        update_until(&mut core, "items after reconnecting", |_| {
+            ARCHIPELAGO_DATA.read().unwrap().blue_orbs == 1
+        });
+        assert_eq!(core.memory.session.borrow().red_orbs, 150);
+        assert!(ARCHIPELAGO_DATA.read().unwrap().items.contains("Shotgun"));
+
+        // A save slot the client hasn't caught up to gets the items from the server again
+        save_handler::await_resync(2);
+        core.resync();
+        update_until(&mut core, "the resync", |_| {
+            server.log.lock().unwrap().syncs == 1
+        });
+        for _ in 0..20 {
+            core.update().unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(CURRENT_INDEX.load(Ordering::SeqCst), 2);
+        assert_eq!(core.memory.session.borrow().red_orbs, 150);
+        // Stayed on the same connection
+        assert_eq!(server.log.lock().unwrap().connects, 2);
+
+        // One that's ahead of the server too doesn't belong to it, everything is received again
+        CURRENT_INDEX.store(5, Ordering::SeqCst);
+        save_handler::await_resync(5);
+        core.resync();
+        update_until(&mut core, "the failed resync", |_| {
+            CURRENT_INDEX.load(Ordering::SeqCst) == 2
+        });
+        assert_eq!(ARCHIPELAGO_DATA.read().unwrap().blue_orbs, 1);
+        assert_eq!(server.log.lock().unwrap().syncs, 2);
+        assert_eq!(server.log.lock().unwrap().connects, 2);
+    }
+}
@@ -1,19 +1,29 @@
 use crate::constants::{
     BasicNothingFunc, Coordinates, Difficulty, EMPTY_COORDINATES, Rank, find_item_by_vals,
 };
-use crate::game_manager::{ItemData, get_mission, get_room, get_track, with_session_read};
+use crate::data::generated_locations;
+use crate::game_manager::{
+    ACTOR_POSITION_OFFSET, ARCHIPELAGO_DATA, ItemData, get_mission, get_room, get_track,
+    with_session, with_session_read,
+};
+use crate::game_memory::{DMC1Memory, GameMemory};
+use crate::mapping::{MAPPING, Mapping};
+use crate::ui::overlay;
 use crate::ui::text_handler;
 use crate::ui::text_handler::REPLACE_TEXT;
-use crate::utilities::{DMC1_ADDRESS, clear_item_slot};
-use crate::{constants, create_hook, hook, location_handler};
+use crate::utilities::{DMC1_ADDRESS, clear_item_slot, is_in_shop};
+use crate::{
+    constants, create_hook, data_storage, hook, location_handler, mission_select, skill_manager,
+};
 use minhook::MH_STATUS;
 use minhook::MinHook;
 use randomizer_utilities::archipelago_utilities::CACHED_LOCATIONS;
 use randomizer_utilities::read_data_from_address;
 use std::fmt::{Display, Formatter};
-use std::sync::OnceLock;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::Sender;
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 pub(crate) static TX_LOCATION: OnceLock<Sender<Location>> = OnceLock::new();
 
@@ -21,8 +31,9 @@ pub(crate) static TX_LOCATION: OnceLock<Sender<Location>> = OnceLock::new();
 pub(crate) enum LocationType {
     Standard,
     MissionComplete,
-    // TODO No concept of SS Ranks in DMC1, should I replace with S Rank checks? Or drop it
-    SSRank,
+    /// Mission completion on a specific difficulty, `item_id` is the difficulty
+    MissionCompleteDifficulty,
+    MissionRank,
     PurchaseItem,
 }
 
@@ -62,7 +73,99 @@ pub const ITEM_PICKUP_ADDR: usize = 0x3d5d80;
 // 0x3c5390 - Also called from inv.
 static ORIGINAL_PICKUP: OnceLock<BasicNothingFunc> = OnceLock::new();
 
-static CALL_COUNT: AtomicU8 = AtomicU8::new(0);
+/// How long an actor has to go without the pickup hook touching it before its pickup is over
+const PICKUP_SETTLE: Duration = Duration::from_millis(1000);
+
+/// A pickup actor that is being, or has just been, collected
+struct HandledPickup {
+    address: usize,
+    coordinates: Coordinates,
+    /// (category, id) the actor held when first seen
+    original: (u8, u8),
+    /// (category, id) it was replaced with, same as original if it wasn't
+    replaced: (u8, u8),
+    /// Last time the pickup hook ran for this actor
+    last_seen: Instant,
+}
+
+impl HandledPickup {
+    /// Whether a hook call is part of this actor's ongoing pickup
+    fn is_same_pickup(
+        &self,
+        address: usize,
+        coordinates: &Coordinates,
+        item: (u8, u8),
+        now: Instant,
+    ) -> bool {
+        self.address == address
+            && self.coordinates.is_near(coordinates)
+            && (self.original == item || self.replaced == item)
+            && now.duration_since(self.last_seen) <= PICKUP_SETTLE
+    }
+}
+
+/// The pickup hook runs several times for every item picked up, this keeps it to one check per item.
+/// Each actor's pickup is tracked on its own: the repeat calls land on the same actor while it's
+/// being collected, once the hook has left it alone for [`PICKUP_SETTLE`] its pickup is done and the
+/// next call for it is a new pickup, even for the same item in the same spot.
+#[derive(Default)]
+struct PickupDetector {
+    room: i32,
+    track: i32,
+    handled: Vec<HandledPickup>,
+}
+
+impl PickupDetector {
+    /// True for the first call of a pickup. The repeat calls will find the actor holding either its
+    /// original item or the one it was swapped to
+    fn is_new_pickup(
+        &mut self,
+        room: i32,
+        track: i32,
+        address: usize,
+        coordinates: Coordinates,
+        item: (u8, u8),
+        now: Instant,
+    ) -> bool {
+        // Actors are rebuilt with the room
+        if room != self.room || track != self.track {
+            self.room = room;
+            self.track = track;
+            self.handled.clear();
+        }
+        if let Some(pickup) = self
+            .handled
+            .iter_mut()
+            .find(|pickup| pickup.is_same_pickup(address, &coordinates, item, now))
+        {
+            pickup.last_seen = now;
+            return false;
+        }
+        // Whatever was at this address before has finished being picked up
+        self.handled.retain(|pickup| pickup.address != address);
+        self.handled.push(HandledPickup {
+            address,
+            coordinates,
+            original: item,
+            replaced: item,
+            last_seen: now,
+        });
+        true
+    }
+
+    fn set_replacement(&mut self, address: usize, item: (u8, u8)) {
+        if let Some(pickup) = self
+            .handled
+            .iter_mut()
+            .find(|pickup| pickup.address == address)
+        {
+            pickup.replaced = item;
+        }
+    }
+}
+
+static PICKUP_DETECTOR: LazyLock<Mutex<PickupDetector>> =
+    LazyLock::new(|| Mutex::new(PickupDetector::default()));
 
 const IGNORED_ITEMS: [ItemData; 2] = [
     // Red Orbs - 1
@@ -79,96 +182,105 @@ const IGNORED_ITEMS: [ItemData; 2] = [
     },
 ];
 
-// This works, just has the issue of running 4 times for some reason
+// Runs 4 times for each pickup for some reason, PICKUP_DETECTOR filters out the repeats
 pub fn item_pickup() {
     // [[dmc1.exe+60ad10]+ac88]+88
-    // Using the DDMK name since I don't have a better one
-    const WEAPON_DATA: usize = 0x60AD10;
     const OFFSET_1: usize = 0xAC88;
     // Points to ItemData
     const CATEGORY_OFFSET: usize = 0x88;
     const ID_OFFSET: usize = 0x89;
-    let call_count = CALL_COUNT.load(Ordering::Relaxed);
-    if call_count == 0 {
-        let data_addr: usize = read_data_from_address(*DMC1_ADDRESS + WEAPON_DATA);
-        let pickup_offset: usize = read_data_from_address(data_addr + OFFSET_1);
-        let category: u8 = read_data_from_address(pickup_offset + CATEGORY_OFFSET);
-        let id: u8 = read_data_from_address(pickup_offset + ID_OFFSET);
-        let item_data = ItemData {
-            id,
+    let data_addr: usize = read_data_from_address(*DMC1_ADDRESS + WEAPON_DATA);
+    let pickup_offset: usize = read_data_from_address(data_addr + OFFSET_1);
+    let category: u8 = read_data_from_address(pickup_offset + CATEGORY_OFFSET);
+    let id: u8 = read_data_from_address(pickup_offset + ID_OFFSET);
+    let coordinates = Coordinates::from_position(read_data_from_address::<[f32; 3]>(
+        pickup_offset + ACTOR_POSITION_OFFSET,
+    ));
+    let item_data = ItemData {
+        id,
+        category,
+        count: 0,
+    };
+    let is_new_pickup = !IGNORED_ITEMS.contains(&item_data)
+        && match PICKUP_DETECTOR.lock() {
+            Ok(mut detector) => detector.is_new_pickup(
+                get_room(),
+                get_track(),
+                pickup_offset,
+                coordinates,
+                (category, id),
+                Instant::now(),
+            ),
+            Err(err) => {
+                log::error!("Failed to get pickup detector: {}", err);
+                false
+            }
+        };
+    if is_new_pickup {
+        log::debug!(
+            "Item pickup: Category: {} ID: {} - Item is: {:?}\nMission: {}, Room: {}, Track: {}, Coordinates: {:?}",
             category,
-            count: 0,
+            id,
+            find_item_by_vals(id, category),
+            get_mission(),
+            get_room(),
+            get_track(),
+            coordinates
+        );
+        // Gather location info
+        let received_item = Location {
+            location_type: LocationType::Standard,
+            item_id: id as u32,
+            item_category: category,
+            room: get_room(),
+            track: get_track(),
+            mission: get_mission() as u32,
+            coordinates,
         };
-        if !IGNORED_ITEMS.contains(&item_data) {
-            log::debug!(
-                "Item pickup: Category: {} ID: {} - Item is: {:?}\nMission: {}, Room: {}, Track: {}",
-                category,
-                id,
-                find_item_by_vals(id, category),
-                get_mission(),
-                get_room(),
-                get_track()
-            );
-            // Gather location info
-            let received_item = Location {
-                location_type: LocationType::Standard,
-                item_id: id as u32,
-                item_category: category,
-                room: get_room(),
-                track: get_track(),
-                mission: get_mission() as u32,
-                coordinates: EMPTY_COORDINATES,
-            };
-            // Send off information
-            send_off_location_coords(received_item);
-
-            // Figure out which location we are at for replacement purposes
-            match crate::AP_CORE.get().unwrap().lock() {
-                Ok(core) => {
-                    if let Some(client) = core.connection.client() {
-                        match location_handler::get_location_name_by_data(&received_item, client) {
-                            Ok(loc_key) => {
-                                // Get the AP item data for that location
-                                let map = CACHED_LOCATIONS.read().unwrap();
-                                let located_item = map.get(loc_key).unwrap();
-                                log::debug!("Actual item name is {}", located_item.item().name());
-                                let data = location_handler::get_mapped_data(loc_key).unwrap();
-                                unsafe {
-                                    randomizer_utilities::replace_single_byte(
-                                        pickup_offset + ID_OFFSET,
-                                        data.id,
-                                    );
-                                    randomizer_utilities::replace_single_byte(
-                                        pickup_offset + CATEGORY_OFFSET,
-                                        data.category,
-                                    );
-                                }
-                                REPLACE_TEXT.store(true, Ordering::Relaxed);
-                                if let Ok(mut txt) = text_handler::FOUND_ITEM.write() {
-                                    *txt = Some(located_item.clone());
-                                }
+        // Send off information
+        send_off_location_coords(received_item);
+
+        // Figure out which location we are at for replacement purposes
+        match crate::AP_CORE.get().unwrap().lock() {
+            Ok(core) => {
+                if let Some(client) = core.client() {
+                    match location_handler::get_location_name_by_data(&received_item, client) {
+                        Ok(loc_key) => {
+                            // Get the AP item data for that location
+                            let map = CACHED_LOCATIONS.read().unwrap();
+                            let located_item = map.get(loc_key).unwrap();
+                            log::debug!("Actual item name is {}", located_item.item().name());
+                            let data = location_handler::get_mapped_data(loc_key).unwrap();
+                            unsafe {
+                                randomizer_utilities::replace_single_byte(
+                                    pickup_offset + ID_OFFSET,
+                                    data.id,
+                                );
+                                randomizer_utilities::replace_single_byte(
+                                    pickup_offset + CATEGORY_OFFSET,
+                                    data.category,
+                                );
                             }
-                            Err(err) => {
-                                log::error!("Failed to get location key: {}", err);
+                            if let Ok(mut detector) = PICKUP_DETECTOR.lock() {
+                                detector.set_replacement(pickup_offset, (data.category, data.id));
                             }
+                            REPLACE_TEXT.store(true, Ordering::Relaxed);
+                            if let Ok(mut txt) = text_handler::FOUND_ITEM.write() {
+                                *txt = Some(located_item.clone());
+                            }
+                        }
+                        Err(err) => {
+                            log::error!("Failed to get location key: {}", err);
                         }
                     }
                 }
-                Err(err) => {
-                    log::error!("Failed to get core: {}", err);
-                }
+            }
+            Err(err) => {
+                log::error!("Failed to get core: {}", err);
             }
         }
     }
 
-    // Only want this to call once, no need to check the same loc
-    if call_count >= 3 {
-        log::debug!("Resetting call count");
-        CALL_COUNT.store(0, Ordering::Relaxed);
-    } else {
-        CALL_COUNT.fetch_add(1, Ordering::Relaxed);
-    }
-
     if let Some(func) = ORIGINAL_PICKUP.get() {
         unsafe { func() }
     }
@@ -297,18 +409,174 @@ fn mission_complete() {
             coordinates: EMPTY_COORDINATES,
             item_category: 0,
         });
+        if let Some(mapping) = MAPPING.read().unwrap().as_ref()
+            && mapping.difficulty_checks
+        {
+            // Clearing a mission on Dante Must Die counts for Hard as well
+            for difficulty in [Difficulty::Hard, Difficulty::DanteMustDie] {
+                if difficulty as u8 <= session.difficulty {
+                    send_off_location_coords(Location {
+                        location_type: LocationType::MissionCompleteDifficulty,
+                        item_id: difficulty as u32,
+                        room: -1,
+                        track: -1,
+                        mission: (session.mission - 1) as u32,
+                        coordinates: EMPTY_COORDINATES,
+                        item_category: 0,
+                    });
+                }
+            }
+        }
+        if let Some(mapping) = MAPPING.read().unwrap().as_ref()
+            && mapping.rank_checks
+            && let Some(rank) = Rank::from_repr(session.rank as usize)
+            && rank.meets(&mapping.minimum_rank)
+        {
+            send_off_location_coords(Location {
+                location_type: LocationType::MissionRank,
+                item_id: u32::MAX,
+                room: -1,
+                track: -1,
+                mission: (session.mission - 1) as u32,
+                coordinates: EMPTY_COORDINATES,
+                item_category: 0,
+            });
+        }
     })
     .unwrap();
+    if let Ok((mission, rank)) = with_session_read(|s| (s.mission - 1, s.rank)) {
+        data_storage::record_mission_complete(mission, Rank::from_repr(rank as usize));
+    }
+    mission_select::clear_selection();
+    if let Some(mapping) = MAPPING.read().unwrap().as_ref()
+        && let Some(next) = redirect_next_mission(&DMC1Memory, mapping)
+    {
+        overlay::set_next_mission(next);
+    }
+}
+
+/// Room and track each mission starts in, indexed by mission - 1. These are the rooms of each mission's first
+/// pickup in locations.json, the missions without pickups (7, 20, 22 and 23) aren't known yet. [`verify_redirect`]
+/// logs where a redirected mission actually started if it doesn't match.
+const MISSION_STARTS: [Option<(u32, u32)>; mission_select::LAST_MISSION as usize] = [
+    Some((33, 1)),
+    Some((17, 1)),
+    Some((12, 1)),
+    Some((16, 1)),
+    Some((18, 1)),
+    Some((11, 1)),
+    None,
+    Some((4, 1)),
+    Some((0, 2)),
+    Some((21, 2)),
+    Some((30, 2)),
+    Some((1, 5)),
+    Some((10, 5)),
+    Some((7, 5)),
+    Some((8, 2)),
+    Some((11, 2)),
+    Some((19, 3)),
+    Some((24, 3)),
+    Some((44, 3)),
+    None,
+    Some((1, 4)),
+    None,
+    None,
+];
+
+/// (room, track) the mission starts in
+pub(crate) fn mission_start(mission: u8) -> Option<(u32, u32)> {
+    MISSION_STARTS
+        .get(mission.checked_sub(1)? as usize)
+        .copied()
+        .flatten()
+}
+
+/// Room and track a redirect should land in, checked once the next room loads
+static PENDING_REDIRECT: Mutex<Option<(u8, u32, u32)>> = Mutex::new(None);
+
+/// For the RandomOrder goal, send the player to the next mission in the order instead of the vanilla one.
+/// Run once the game has moved on to the following mission, gives the mission it was redirected to
+pub(crate) fn redirect_next_mission<M: GameMemory>(memory: &M, mapping: &Mapping) -> Option<u8> {
+    let completed = memory.mission().checked_sub(1)?;
+    let next = mapping.next_mission(completed)?;
+    log::debug!("Redirecting to Mission #{}", next);
+    start_mission_in(memory, next);
+    Some(next)
+}
+
+/// Make `mission` the one the game starts next. The mission number alone doesn't move Dante, the game goes
+/// wherever the event data says next, so that gets pointed at the mission's first room as well.
+pub(crate) fn start_mission_at(mission: u8) {
+    start_mission_in(&DMC1Memory, mission);
 }
 
+fn start_mission_in<M: GameMemory>(memory: &M, mission: u8) {
+    if let Err(err) = memory.with_session(|s| s.mission = mission) {
+        log::error!("Failed to set mission: {:?}", err);
+        return;
+    }
+    let Some((room, track)) = mission_start(mission) else {
+        log::warn!(
+            "Don't know where Mission #{} starts, it will start from the vanilla room",
+            mission
+        );
+        return;
+    };
+    match memory.set_next_room(room, track) {
+        Ok(_) => *PENDING_REDIRECT.lock().unwrap() = Some((mission, room, track)),
+        Err(err) => log::error!("Failed to set next room: {:?}", err),
+    }
+}
+
+/// Called when a room loads, makes sure a redirected mission actually started where it should have
+pub(crate) fn verify_redirect() {
+    let Some((mission, room, track)) = PENDING_REDIRECT.lock().unwrap().take() else {
+        return;
+    };
+    let (current_room, current_track) = (get_room(), get_track());
+    if current_room != room as i32 || current_track != track as i32 {
+        log::error!(
+            "Redirect to Mission #{} expected room {} track {}, but loaded room {} track {}",
+            mission,
+            room,
+            track,
+            current_room,
+            current_track
+        );
+    }
+}
+
+// Using the DDMK name since I don't have a better one
+const WEAPON_DATA: usize = 0x60AD10;
+// Highlighted item and store tab in the Divinity Statue menu
+const PURCHASE_IDX_OFFSET: usize = 0xAA6D;
+const PURCHASE_MENU_IDX_OFFSET: usize = 0xAA6C;
+
 static PURCHASE_ITEM_ADDR: usize = 0x3DF5B0; // Called every attempted purchase
 static ORIGINAL_PURCHASE_ITEM: OnceLock<BasicNothingFunc> = OnceLock::new();
 
 pub fn purchase_item() {
-    const WEAPON_DATA: usize = 0x60AD10;
-    const PURCHASE_IDX_OFFSET: usize = 0xAA6D;
-    const PURCHASE_MENU_IDX_OFFSET: usize = 0xAA6C;
-    let orig_red_orbs = with_session_read(|session| session.red_orbs).unwrap();
+    if !is_in_shop() {
+        log::warn!("Purchase hook ran outside of the shop, not treating it as a purchase");
+        if let Some(orig) = ORIGINAL_PURCHASE_ITEM.get() {
+            unsafe {
+                orig();
+            }
+        }
+        return;
+    }
+    let randomize_skills = MAPPING
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|mapping| mapping.randomize_skills);
+    if randomize_skills {
+        // Let the shop work off what's been bought rather than what's been received, so it sells the next level
+        with_session(|s| s.expertise = skill_manager::purchased_expertise()).unwrap();
+    }
+    let (orig_red_orbs, orig_expertise) =
+        with_session_read(|session| (session.red_orbs, session.expertise)).unwrap();
     if let Some(orig) = ORIGINAL_PURCHASE_ITEM.get() {
         unsafe {
             orig();
@@ -338,18 +606,77 @@ pub fn purchase_item() {
                     });
                 }
             }
-            // TODO Buying skill checks
-            constants::ALASTOR_STORE => {
-                // Skill purchases do not differentiate between skill levels
-                log::debug!("Alastor skill purchase: {idx}");
-            }
-            constants::IFRIT_STORE => {
-                // Skill purchases do not differentiate between skill levels
-                log::debug!("Ifrit skill purchase: {idx}");
+            constants::ALASTOR_STORE | constants::IFRIT_STORE => {
+                // Skill purchases do not differentiate between skill levels, so look at which expertise flag was just set
+                let new_expertise = with_session_read(|s| s.expertise).unwrap();
+                match skill_manager::find_new_skill(&orig_expertise, &new_expertise) {
+                    Some(skill_id) => {
+                        log::debug!("Skill purchase: {}", skill_id);
+                        send_off_location_coords(Location {
+                            location_type: LocationType::PurchaseItem,
+                            item_id: skill_id as u32,
+                            mission: 0,
+                            room: 0,
+                            coordinates: EMPTY_COORDINATES,
+                            track: 0,
+                            item_category: category,
+                        });
+                        skill_manager::add_purchased_skill(skill_id);
+                    }
+                    None => {
+                        log::error!(
+                            "Skill purchase ({idx}) did not set any expertise: {:?} - {:?}",
+                            orig_expertise,
+                            new_expertise
+                        );
+                    }
+                }
             }
             _ => unreachable!(),
         }
     }
+    if randomize_skills {
+        // The randomized item will be given instead, so Dante goes back to the skills he's received
+        skill_manager::restore_after_purchase(&DMC1Memory, &ARCHIPELAGO_DATA.read().unwrap());
+    }
+}
+
+/// Get the location that buying the highlighted store item would check, None if it isn't a check
+pub(crate) fn get_shop_preview() -> Option<&'static str> {
+    let data_addr: usize = read_data_from_address(*DMC1_ADDRESS + WEAPON_DATA);
+    let idx: u8 = read_data_from_address(data_addr + PURCHASE_IDX_OFFSET);
+    let category: u8 = read_data_from_address(data_addr + PURCHASE_MENU_IDX_OFFSET);
+    if category != constants::EXTRA_STORE {
+        return None;
+    }
+    let (bought, max) = match idx {
+        5 => (
+            with_session_read(|s| s.bought_hp).ok()?,
+            generated_locations::BLUE_ORB_PURCHASES,
+        ),
+        6 => (
+            with_session_read(|s| s.bought_magic).ok()?,
+            generated_locations::PURPLE_ORB_PURCHASES,
+        ),
+        _ => return None,
+    };
+    if bought >= max {
+        return None;
+    }
+    location_handler::resolve_location_name(
+        &Location {
+            location_type: LocationType::PurchaseItem,
+            item_id: idx as u32,
+            mission: (bought + 1) as u32,
+            room: 0,
+            coordinates: EMPTY_COORDINATES,
+            track: 0,
+            item_category: category,
+        },
+        |_| None,
+        |_| false,
+    )
+    .ok()
 }
 
 fn send_off_location_coords(loc: Location) {
@@ -357,3 +684,162 @@ fn send_off_location_coords(loc: Location) {
         tx.send(loc).expect("Failed to send Location!");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_memory::MockMemory;
+
+    const ROOM: i32 = 3;
+    const TRACK: i32 = 1;
+    const VITAL_STAR: (u8, u8) = (3, 0);
+    const BLUE_ORB: (u8, u8) = (3, 5);
+    const SPOT: Coordinates = Coordinates {
+        x: 100,
+        y: 0,
+        z: -200,
+    };
+    const OTHER_SPOT: Coordinates = Coordinates {
+        x: 900,
+        y: 0,
+        z: 400,
+    };
+
+    fn pickup(
+        detector: &mut PickupDetector,
+        address: usize,
+        coordinates: Coordinates,
+        item: (u8, u8),
+        now: Instant,
+    ) -> bool {
+        detector.is_new_pickup(ROOM, TRACK, address, coordinates, item, now)
+    }
+
+    #[test]
+    fn repeat_calls_are_one_pickup() {
+        let mut detector = PickupDetector::default();
+        let now = Instant::now();
+        let seen: Vec<bool> = (0..4)
+            .map(|frame| {
+                let at = now + Duration::from_millis(16 * frame);
+                pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, at)
+            })
+            .collect();
+        assert_eq!(seen, [true, false, false, false]);
+    }
+
+    #[test]
+    fn replaced_item_is_the_same_pickup() {
+        let mut detector = PickupDetector::default();
+        let now = Instant::now();
+        assert!(pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, now));
+        detector.set_replacement(0x1000, BLUE_ORB);
+        assert!(!pickup(&mut detector, 0x1000, SPOT, BLUE_ORB, now));
+        assert!(!pickup(&mut detector, 0x1000, SPOT, BLUE_ORB, now));
+    }
+
+    #[test]
+    fn same_item_picked_up_again_is_new() {
+        let mut detector = PickupDetector::default();
+        let now = Instant::now();
+        for call in 0..4 {
+            assert_eq!(
+                pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, now),
+                call == 0
+            );
+        }
+        // A second Vital Star dropped in the same spot and reusing the actor
+        let later = now + PICKUP_SETTLE * 2;
+        for call in 0..4 {
+            assert_eq!(
+                pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, later),
+                call == 0
+            );
+        }
+    }
+
+    #[test]
+    fn interleaved_pickups_are_kept_apart() {
+        let mut detector = PickupDetector::default();
+        let now = Instant::now();
+        assert!(pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, now));
+        assert!(pickup(&mut detector, 0x2000, OTHER_SPOT, VITAL_STAR, now));
+        assert!(!pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, now));
+        assert!(!pickup(&mut detector, 0x2000, OTHER_SPOT, VITAL_STAR, now));
+        assert!(!pickup(&mut detector, 0x2000, OTHER_SPOT, VITAL_STAR, now));
+        assert!(!pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, now));
+    }
+
+    #[test]
+    fn new_actor_at_a_reused_address_is_new() {
+        let mut detector = PickupDetector::default();
+        let now = Instant::now();
+        assert!(pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, now));
+        assert!(pickup(&mut detector, 0x1000, OTHER_SPOT, BLUE_ORB, now));
+        assert!(!pickup(&mut detector, 0x1000, OTHER_SPOT, BLUE_ORB, now));
+    }
+
+    #[test]
+    fn room_change_forgets_pickups() {
+        let mut detector = PickupDetector::default();
+        let now = Instant::now();
+        assert!(pickup(&mut detector, 0x1000, SPOT, VITAL_STAR, now));
+        assert!(detector.is_new_pickup(ROOM + 1, TRACK, 0x1000, SPOT, VITAL_STAR, now));
+    }
+
+    fn random_order(order: &[u8]) -> Mapping {
+        serde_json::from_value(serde_json::json!({
+            "starter_items": [],
+            "randomize_skills": false,
+            "purple_orb_mode": false,
+            "devil_trigger_mode": false,
+            "death_link": 0,
+            "goal": 2,
+            "mission_order": order,
+        }))
+        .unwrap()
+    }
+
+    /// Where the game goes after a mission when nothing redirects it
+    const VANILLA_NEXT: (u32, u32) = (999, 9);
+
+    fn expected_next_room(mission: u8) -> (u32, u32) {
+        mission_start(mission).unwrap_or(VANILLA_NEXT)
+    }
+
+    #[test]
+    fn random_order_redirects_every_transition() {
+        let order: Vec<u8> = vec![
+            5, 12, 1, 20, 9, 3, 17, 22, 7, 14, 2, 19, 11, 23, 6, 16, 10, 4, 21, 8, 15, 13, 18,
+        ];
+        let mapping = random_order(&order);
+        let memory = MockMemory::new();
+
+        // New game
+        memory.next_room.set(VANILLA_NEXT);
+        start_mission_in(&memory, mapping.first_mission().unwrap());
+        assert_eq!(memory.mission(), order[0]);
+        assert_eq!(memory.next_room.get(), expected_next_room(order[0]));
+
+        for pair in order.windows(2) {
+            let (completed, next) = (pair[0], pair[1]);
+            // The game moves on to the vanilla next mission by itself
+            memory.session.borrow_mut().mission = completed + 1;
+            memory.next_room.set(VANILLA_NEXT);
+            assert_eq!(redirect_next_mission(&memory, &mapping), Some(next));
+            assert_eq!(memory.mission(), next);
+            assert_eq!(memory.next_room.get(), expected_next_room(next));
+        }
+
+        // Nothing after the last mission in the order
+        memory.session.borrow_mut().mission = order[order.len() - 1] + 1;
+        assert_eq!(redirect_next_mission(&memory, &mapping), None);
+    }
+
+    #[test]
+    fn mission_starts_are_one_based() {
+        assert_eq!(mission_start(0), None);
+        assert_eq!(mission_start(1), Some((33, 1)));
+        assert_eq!(mission_start(mission_select::LAST_MISSION + 1), None);
+    }
+}
@@ -4,7 +4,11 @@ use crate::constants::ItemCategory;
 use crate::game_manager::{
     ItemData, with_active_player_data, with_active_player_data_read, with_session_read,
 };
-use crate::{config, constants, game_manager, utilities};
+use crate::mapping::MAPPING;
+use crate::save_manager::SAVE_MANAGER;
+use crate::{
+    config, constants, data_storage, game_manager, mission_select, save_manager, utilities,
+};
 use imgui_sys::{ImGuiCond, ImGuiCond_Appearing, ImGuiWindowFlags, ImVec2};
 use randomizer_utilities::dmc::common_ddmk;
 use randomizer_utilities::dmc::common_ddmk::{
@@ -57,6 +61,7 @@ unsafe extern "C" fn hooked_render() {
 
         archipelago_window(CUSTOM_ITEM.lock().unwrap()); // For the archipelago window
         tracking_window();
+        save_manager_window();
         match get_orig_render_func() {
             None => {}
             Some(fnc) => {
@@ -90,8 +95,22 @@ unsafe fn tracking_window() {
                         .join("  ");
                     common_ddmk::text(format!("{}\0", row_text));
                 }
+                if let Some(mapping) = MAPPING.read().unwrap().as_ref()
+                    && !mapping.starter_items.is_empty()
+                {
+                    common_ddmk::text(format!(
+                        "Starter Items: {}\0",
+                        mapping.starter_items.join(", ")
+                    ));
+                }
                 common_ddmk::text(format!("Blue Orbs: {}\0", data.blue_orbs));
                 common_ddmk::text(format!("Purple Orbs: {}\0", data.purple_orbs));
+                let progress = data_storage::get_progress();
+                common_ddmk::text(format!(
+                    "Missions Completed: {}\0",
+                    progress.missions_completed.len()
+                ));
+                common_ddmk::text(format!("Deaths: {}\0", progress.deaths));
                 // HP Trackers
                 with_session_read(|s| {
                     common_ddmk::text(format!("S HP: {}\0", s.hp));
@@ -112,6 +131,114 @@ unsafe fn tracking_window() {
     }
 }
 
+/// Button with a label made at runtime, the label needs to end with \0
+unsafe fn button(label: &str) -> bool {
+    unsafe {
+        common_ddmk::get_imgui_button()(label.as_ptr() as *const c_char, &ImVec2 { x: 0.0, y: 0.0 })
+    }
+}
+
+unsafe fn save_manager_window() {
+    unsafe {
+        save_manager::refresh_if_needed();
+        let flag = &mut true;
+        common_ddmk::get_imgui_next_pos()(
+            &ImVec2 {
+                x: 1100.0,
+                y: 100.0,
+            },
+            ImGuiCond_Appearing as ImGuiCond,
+            &ImVec2 { x: 0.0, y: 0.0 },
+        );
+        common_ddmk::get_imgui_begin()(
+            c"Saves".as_ptr() as *const c_char,
+            flag as *mut bool,
+            imgui_sys::ImGuiWindowFlags_AlwaysAutoResize as ImGuiWindowFlags,
+        );
+        match SAVE_MANAGER.lock() {
+            Ok(mut manager) => {
+                if button("Refresh\0") {
+                    thread::spawn(save_manager::refresh);
+                }
+                if manager.saves.is_empty() {
+                    common_ddmk::text("No saves found\0".to_string());
+                }
+                for (i, entry) in manager.saves.clone().into_iter().enumerate() {
+                    common_ddmk::text(format!(
+                        "{} ({}) - Mission {} - {}\0",
+                        entry.slot_name,
+                        entry.seed,
+                        match entry.mission {
+                            0 => "-".to_string(),
+                            mission => mission.to_string(),
+                        },
+                        save_manager::format_age(entry.last_played)
+                    ));
+                    if let Some(err) = &entry.error {
+                        common_ddmk::text(format!("Damaged: {}\0", err));
+                    }
+                    if button(&format!("Export##{}\0", i)) {
+                        let entry = entry.clone();
+                        save_manager::run_action(move || {
+                            save_manager::export_save(&entry)
+                                .map(|path| format!("Exported to {}", path.display()))
+                        });
+                    }
+                    if manager.pending_rename.as_ref() == Some(&entry.path) {
+                        input_rs(format!("New slot name##{}\0", i), &mut manager.rename_to);
+                        if button(&format!("Confirm Rename##{}\0", i)) {
+                            manager.pending_rename = None;
+                            let slot_name = std::mem::take(&mut manager.rename_to);
+                            let entry = entry.clone();
+                            save_manager::run_action(move || {
+                                save_manager::rename_save(&entry, &slot_name)
+                                    .map(|path| format!("Renamed to {}", path.display()))
+                            });
+                        }
+                    } else if button(&format!("Rename##{}\0", i)) {
+                        manager.pending_rename = Some(entry.path.clone());
+                        manager.rename_to = entry.slot_name.clone();
+                    }
+                    // Deleting takes two presses
+                    if manager.pending_delete.as_ref() == Some(&entry.path) {
+                        if button(&format!("Confirm Delete##{}\0", i)) {
+                            manager.pending_delete = None;
+                            save_manager::run_action(move || {
+                                save_manager::delete_save(&entry).map(|_| {
+                                    format!("Moved {} to the deleted folder", entry.path.display())
+                                })
+                            });
+                        }
+                    } else if button(&format!("Delete##{}\0", i)) {
+                        manager.pending_delete = Some(entry.path.clone());
+                    }
+                }
+                common_ddmk::text("Imports (files in archipelago/import)\0".to_string());
+                for (i, path) in manager.imports.iter().enumerate() {
+                    common_ddmk::text(format!(
+                        "{}\0",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    ));
+                    if button(&format!("Import##{}\0", i)) {
+                        let path = path.clone();
+                        save_manager::run_action(move || {
+                            save_manager::import_save(&path)
+                                .map(|saved| format!("Imported to {}", saved.display()))
+                        });
+                    }
+                }
+                if !manager.status.is_empty() {
+                    common_ddmk::text(format!("{}\0", manager.status));
+                }
+            }
+            Err(err) => {
+                log::error!("Failed to get save manager: {:?}", err);
+            }
+        }
+        common_ddmk::get_imgui_end()();
+    }
+}
+
 unsafe fn archipelago_window(mut custom_item_data: MutexGuard<CustomDataHolder>) {
     unsafe {
         let flag = &mut true;
@@ -133,6 +260,27 @@ unsafe fn archipelago_window(mut custom_item_data: MutexGuard<CustomDataHolder>)
                 "Disconnected"
             }
         ));
+        if mission_select::is_free_select() {
+            common_ddmk::text(format!(
+                "Mission Select: {}\0",
+                match mission_select::get_selected_mission() {
+                    Some(mission) => format!("#{}", mission),
+                    None => "None".to_string(),
+                }
+            ));
+            if common_ddmk::get_imgui_button()(
+                c"Previous Mission".as_ptr() as *const c_char,
+                &ImVec2 { x: 0.0, y: 0.0 },
+            ) {
+                thread::spawn(|| mission_select::cycle_mission(false));
+            }
+            if common_ddmk::get_imgui_button()(
+                c"Next Mission".as_ptr() as *const c_char,
+                &ImVec2 { x: 0.0, y: 0.0 },
+            ) {
+                thread::spawn(|| mission_select::cycle_mission(true));
+            }
+        }
         const DEBUG: bool = true;
         if DEBUG {
             input_rs("Category\0", &mut custom_item_data.category);
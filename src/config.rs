@@ -1,6 +1,14 @@
+use crate::constants::Difficulty;
+use crate::mapping::HintScouts;
 use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
 use std::sync::LazyLock;
 
+/// The file randomizer_utilities::load_config reads the config from
+const CONFIG_FILE: &str = "dmc1_randomizer.json";
+
 pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
     randomizer_utilities::load_config("dmc1_randomizer").unwrap_or_else(|err| {
         log::error!("Failed to load config: {}", err);
@@ -10,28 +18,106 @@ pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Connection {
-    pub port: i32,                  // The port the local client is running on
+    pub port: i32,                       // The port the local client is running on
     pub address: String, // The address the local client is on, should always be localhost
-    pub disable_auto_connect: bool, // Do not attempt to connect to local client
-    // TODO Forgot to reimplement this
-    pub reconnect_interval_seconds: i32, // How many seconds between each reconnection attempt to the local client
+    pub disable_auto_connect: bool, // Do not connect on launch, wait for the in-game connection screen
+    pub reconnect_interval_seconds: i32, // Base delay between reconnection attempts, doubles after each failure
+    #[serde(default)]
+    pub slot_name: String, // Slot to connect as, can be left empty when the local client fills it in
+    #[serde(default)]
+    pub password: String, // Room password, if there is one
+    #[serde(default)]
+    pub server_url: Option<String>, // Connect straight to a MultiServer instead (e.g. wss://archipelago.gg:38281)
 }
 
 impl Connection {
+    /// The direct server URL if one is set, otherwise the local client
     pub fn get_url(&self) -> String {
-        format!("{}:{}", self.address, self.port)
+        match self.server_url.as_deref().map(str::trim) {
+            Some(url) if !url.is_empty() => url.to_string(),
+            _ => format!("{}:{}", self.address, self.port),
+        }
+    }
+
+    /// Keep the URL as the local client's address when that's what was used
+    fn set_server_details(&mut self, details: &ServerDetails) {
+        let local = format!("{}:{}", self.address, self.port);
+        self.server_url = (details.url != local).then(|| details.url.clone());
+        self.slot_name = details.slot_name.clone();
+        self.password = details.password.clone();
+    }
+
+    pub fn get_server_details(&self) -> ServerDetails {
+        ServerDetails {
+            url: self.get_url(),
+            slot_name: self.slot_name.clone(),
+            password: self.password.clone(),
+        }
     }
 }
 
+/// Everything needed to open a connection to a room
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ServerDetails {
+    pub url: String,
+    pub slot_name: String,
+    pub password: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Mods {
     pub disable_ddmk_hooks: bool, // Stop DDMK hooks from being loaded
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Hints {
+    pub scouts: Option<HintScouts>, // Overrides the slot's setting for which scouts create hints (Off, Shop or All)
+}
+
+/// How hard a received DeathLink hits when the slot uses HurtLink
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct HurtLink {
+    pub easy_percent: f32, // Percentage of max HP taken on each difficulty
+    pub normal_percent: f32,
+    pub hard_percent: f32,
+    pub dante_must_die_percent: f32,
+    pub flat_damage: u16,   // Extra damage on top of the percentage
+    pub never_lethal: bool, // Always leave Dante with at least 1 HP
+}
+
+impl HurtLink {
+    pub(crate) fn percent_for(&self, difficulty: Difficulty) -> f32 {
+        match difficulty {
+            Difficulty::Easy => self.easy_percent,
+            Difficulty::Normal => self.normal_percent,
+            Difficulty::Hard => self.hard_percent,
+            Difficulty::DanteMustDie => self.dante_must_die_percent,
+        }
+    }
+}
+
+impl Default for HurtLink {
+    fn default() -> HurtLink {
+        HurtLink {
+            easy_percent: 25.0,
+            normal_percent: 33.0,
+            hard_percent: 50.0,
+            dante_must_die_percent: 83.0,
+            flat_damage: 0,
+            never_lethal: true,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub connections: Connection,
     pub mods: Mods,
+    #[serde(default)]
+    pub hints: Hints,
+    #[serde(default)]
+    pub hurt_link: HurtLink,
 }
 
 impl Default for Config {
@@ -42,10 +128,84 @@ impl Default for Config {
                 address: "localhost".to_string(),
                 disable_auto_connect: false,
                 reconnect_interval_seconds: 10,
+                slot_name: String::new(),
+                password: String::new(),
+                server_url: None,
             },
             mods: Mods {
                 disable_ddmk_hooks: false,
             },
+            hints: Hints::default(),
+            hurt_link: HurtLink::default(),
+        }
+    }
+}
+
+/// Details from the config file, which has the last successful connection in it
+pub fn get_last_server_details() -> ServerDetails {
+    CONFIG.connections.get_server_details()
+}
+
+/// Put the server, slot and password of a successful connection into the config file
+pub fn save_server_details(details: &ServerDetails) -> Result<(), Box<dyn Error>> {
+    // Read it again rather than using CONFIG, so edits made while the game is running aren't lost
+    let mut config: Config = match fs::read_to_string(CONFIG_FILE) {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(err) if err.kind() == ErrorKind::NotFound => Config::default(),
+        Err(err) => return Err(err.into()),
+    };
+    config.connections.set_server_details(details);
+    fs::write(CONFIG_FILE, serde_json::to_string_pretty(&config)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn details(url: &str) -> ServerDetails {
+        ServerDetails {
+            url: url.to_string(),
+            slot_name: "Dante".to_string(),
+            password: "hunter2".to_string(),
         }
     }
+
+    #[test]
+    fn server_url_is_kept_for_a_direct_connection() {
+        let mut config = Config::default();
+        config
+            .connections
+            .set_server_details(&details("wss://archipelago.gg:38281"));
+        assert_eq!(
+            config.connections.server_url.as_deref(),
+            Some("wss://archipelago.gg:38281")
+        );
+        assert_eq!(config.connections.slot_name, "Dante");
+        assert_eq!(config.connections.get_url(), "wss://archipelago.gg:38281");
+    }
+
+    #[test]
+    fn local_client_address_clears_the_server_url() {
+        let mut config = Config::default();
+        config.connections.server_url = Some("wss://archipelago.gg:38281".to_string());
+        let local = format!("{}:{}", config.connections.address, config.connections.port);
+        config.connections.set_server_details(&details(&local));
+        assert_eq!(config.connections.server_url, None);
+        assert_eq!(config.connections.get_url(), local);
+    }
+
+    #[test]
+    fn details_round_trip_through_the_file() {
+        let mut config = Config::default();
+        let saved = details("wss://archipelago.gg:38281");
+        config.connections.set_server_details(&saved);
+        assert_eq!(config.connections.password, "hunter2");
+        let json = serde_json::to_string_pretty(&config).unwrap();
+        let loaded: Config = serde_json::from_str(&json).unwrap();
+        let details = loaded.connections.get_server_details();
+        assert_eq!(details.url, saved.url);
+        assert_eq!(details.slot_name, saved.slot_name);
+        assert_eq!(details.password, saved.password);
+    }
 }
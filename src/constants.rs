@@ -1,6 +1,7 @@
 use crate::game_manager::ItemData;
 use bimap::BiMap;
 use randomizer_utilities::dmc::dmc_constants::GameConfig;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::LazyLock;
 
@@ -462,17 +463,39 @@ pub struct ItemEntry {
 }
 
 pub const EMPTY_COORDINATES: Coordinates = Coordinates { x: 0, y: 0, z: 0 };
+/// How far (in world units) a pickup can be from the coordinates in locations.json and still match
+pub const COORDINATE_TOLERANCE: i64 = 100;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Coordinates {
-    pub(crate) x: u32,
-    pub(crate) y: u32,
-    pub(crate) z: u32,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) z: i32,
 }
 
 impl Coordinates {
+    /// Rounds a world position to the whole units used in locations.json
+    pub fn from_position(position: [f32; 3]) -> Coordinates {
+        Coordinates {
+            x: position[0].round() as i32,
+            y: position[1].round() as i32,
+            z: position[2].round() as i32,
+        }
+    }
+
     pub fn has_coords(&self) -> bool {
-        self.x > 0
+        *self != EMPTY_COORDINATES
+    }
+
+    pub fn distance_squared(&self, other: &Coordinates) -> i64 {
+        let dx = (self.x - other.x) as i64;
+        let dy = (self.y - other.y) as i64;
+        let dz = (self.z - other.z) as i64;
+        dx * dx + dy * dy + dz * dz
+    }
+
+    pub fn is_near(&self, other: &Coordinates) -> bool {
+        self.distance_squared(other) <= COORDINATE_TOLERANCE * COORDINATE_TOLERANCE
     }
 }
 
@@ -523,7 +546,16 @@ pub static GUN_MAP: LazyLock<BiMap<&str, u8>> = LazyLock::new(|| {
     map
 });
 
-#[derive(Copy, Clone, strum_macros::Display, strum_macros::FromRepr)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Deserialize,
+    Serialize,
+    strum_macros::Display,
+    strum_macros::FromRepr,
+)]
 pub(crate) enum Rank {
     S = 0,
     A = 1,
@@ -531,3 +563,10 @@ pub(crate) enum Rank {
     C = 3,
     D = 4,
 }
+
+impl Rank {
+    /// If this rank is the same or better than the other one
+    pub(crate) fn meets(&self, minimum: &Rank) -> bool {
+        (*self as i32) <= (*minimum as i32)
+    }
+}
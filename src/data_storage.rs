@@ -0,0 +1,458 @@
+use crate::constants::Rank;
+use crate::mapping::Mapping;
+use crate::ui::hints_panel;
+use crate::ui::hints_panel::HintEntry;
+use archipelago_rs::{Client, DataStorageOperation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::thread;
+
+/// This slot's progress, kept in data storage so trackers can follow along without reading game memory
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct SlotProgress {
+    /// Mission currently being played
+    pub(crate) mission: u8,
+    pub(crate) deaths: u32,
+    pub(crate) missions_completed: Vec<u8>,
+    /// Best rank gotten in each mission
+    pub(crate) ranks: BTreeMap<u8, Rank>,
+}
+
+impl SlotProgress {
+    /// Combine with progress from elsewhere (the server or another session), keeping the most of everything
+    fn merge(&mut self, other: SlotProgress) {
+        self.deaths = self.deaths.max(other.deaths);
+        for mission in other.missions_completed {
+            if !self.missions_completed.contains(&mission) {
+                self.missions_completed.push(mission);
+            }
+        }
+        self.missions_completed.sort();
+        for (mission, rank) in other.ranks {
+            self.ranks
+                .entry(mission)
+                .and_modify(|best| {
+                    if rank.meets(best) {
+                        *best = rank
+                    }
+                })
+                .or_insert(rank);
+        }
+    }
+
+    /// Merge progress read back from data storage, the mission being played is whatever this session says it is
+    fn merge_remote(&mut self, remote: SlotProgress) {
+        let mission = self.mission;
+        self.merge(remote);
+        self.mission = mission;
+    }
+}
+
+#[derive(Default)]
+struct ProgressState {
+    /// Data storage key the progress belongs to, a different slot starts fresh
+    key: String,
+    progress: SlotProgress,
+}
+
+static PROGRESS: LazyLock<RwLock<ProgressState>> =
+    LazyLock::new(|| RwLock::new(ProgressState::default()));
+/// Set when the progress changes and needs to be sent to the server
+static PROGRESS_DIRTY: AtomicBool = AtomicBool::new(false);
+
+/// Key the progress is stored under, external trackers can watch this
+fn progress_key(client: &Client<Mapping>) -> String {
+    format!(
+        "dmc1_progress_{}_{}",
+        client.this_player().team(),
+        client.this_player().slot()
+    )
+}
+
+pub(crate) fn get_progress() -> SlotProgress {
+    PROGRESS
+        .read()
+        .map(|state| state.progress.clone())
+        .unwrap_or_default()
+}
+
+fn update_progress<F>(f: F)
+where
+    F: FnOnce(&mut SlotProgress),
+{
+    match PROGRESS.write() {
+        Ok(mut state) => {
+            let before = state.progress.clone();
+            f(&mut state.progress);
+            if state.progress != before {
+                PROGRESS_DIRTY.store(true, Ordering::SeqCst);
+            }
+        }
+        Err(err) => log::error!("Failed to update progress: {}", err),
+    }
+}
+
+pub(crate) fn set_current_mission(mission: u8) {
+    update_progress(|progress| progress.mission = mission);
+}
+
+pub(crate) fn record_death() {
+    update_progress(|progress| progress.deaths += 1);
+}
+
+pub(crate) fn record_mission_complete(mission: u8, rank: Option<Rank>) {
+    update_progress(|progress| {
+        progress.merge(SlotProgress {
+            missions_completed: vec![mission],
+            ranks: rank
+                .map(|rank| BTreeMap::from([(mission, rank)]))
+                .unwrap_or_default(),
+            ..Default::default()
+        })
+    });
+}
+
+/// Start following the progress key and pull in whatever the server already has for it
+pub(crate) fn watch_progress(client: &mut Client<Mapping>) {
+    let key = progress_key(client);
+    if let Ok(mut state) = PROGRESS.write()
+        && state.key != key
+    {
+        *state = ProgressState {
+            key: key.clone(),
+            progress: SlotProgress::default(),
+        };
+    }
+    if let Err(err) = client.set_notify(vec![key.clone()]) {
+        log::error!("Failed to watch progress: {}", err);
+        return;
+    }
+    let receiver = client.get(vec![key.clone()]);
+    thread::spawn(move || match receiver.recv() {
+        Ok(Ok(mut values)) => {
+            if let Some(value) = values.remove(&key) {
+                merge_remote_progress(value);
+            }
+            // Anything done while offline still needs to go up
+            PROGRESS_DIRTY.store(true, Ordering::SeqCst);
+        }
+        Ok(Err(err)) => log::error!("Failed to get progress: {}", err),
+        Err(err) => log::error!("Progress request was dropped: {}", err),
+    });
+}
+
+fn merge_remote_progress(value: Value) {
+    if value.is_null() {
+        return;
+    }
+    match serde_json::from_value::<SlotProgress>(value) {
+        Ok(remote) => {
+            if let Ok(mut state) = PROGRESS.write() {
+                state.progress.merge_remote(remote);
+            }
+        }
+        Err(err) => log::error!("Unable to read progress from data storage: {}", err),
+    }
+}
+
+/// Send the progress off if it changed since the last time. Run by the core every frame
+pub(crate) fn sync_progress(client: &mut Client<Mapping>) {
+    if !PROGRESS_DIRTY.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let value = match serde_json::to_value(get_progress()) {
+        Ok(value) => value,
+        Err(err) => {
+            log::error!("Failed to serialize progress: {}", err);
+            return;
+        }
+    };
+    let key = progress_key(client);
+    if let Err(err) = client.set(
+        key,
+        Value::Null,
+        false,
+        vec![DataStorageOperation::Replace(value)],
+    ) {
+        log::error!("Failed to send progress: {}", err);
+        PROGRESS_DIRTY.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Key the server keeps this slot's hints under. Read-only for clients
+fn hint_key(client: &Client<Mapping>) -> String {
+    format!(
+        "_read_hints_{}_{}",
+        client.this_player().team(),
+        client.this_player().slot()
+    )
+}
+
+/// Get told whenever a hint for or from this slot changes, and fetch the ones that already exist
+pub(crate) fn watch_hints(client: &mut Client<Mapping>) {
+    let key = hint_key(client);
+    if let Err(err) = client.set_notify(vec![key.clone()]) {
+        log::error!("Failed to watch hints: {}", err);
+        return;
+    }
+    let receiver = client.get(vec![key.clone()]);
+    thread::spawn(move || match receiver.recv() {
+        Ok(Ok(mut values)) => {
+            if let Some(value) = values.remove(&key) {
+                *FETCHED_HINTS.lock().unwrap() = Some(value);
+            }
+        }
+        Ok(Err(err)) => log::error!("Failed to get hints: {}", err),
+        Err(err) => log::error!("Hint request was dropped: {}", err),
+    });
+}
+
+/// Hints fetched when connecting, the client is needed to put names to them
+static FETCHED_HINTS: Mutex<Option<Value>> = Mutex::new(None);
+
+/// Show any hints that have been fetched since the last frame
+pub(crate) fn update_hints(client: &Client<Mapping>) {
+    if let Some(value) = FETCHED_HINTS.lock().unwrap().take() {
+        hints_panel::set_hints(parse_hints(client, value));
+    }
+}
+
+pub(crate) fn handle_key_changed(client: &Client<Mapping>, key: &str, new_value: Value) {
+    if key == hint_key(client) {
+        hints_panel::set_hints(parse_hints(client, new_value));
+    } else if key == progress_key(client) {
+        // Could be an echo of our own update, merging that changes nothing
+        merge_remote_progress(new_value);
+    }
+}
+
+/// Puts names to the ids in a hint. Slots are in this slot's team
+trait HintNames {
+    fn player_name(&self, slot: i64) -> Option<String>;
+    /// Items are named by the game of the player receiving them
+    fn item_name(&self, receiver: i64, item: i64) -> Option<String>;
+    /// Locations are named by the game of the player whose world they're in
+    fn location_name(&self, finder: i64, location: i64) -> Option<String>;
+}
+
+impl HintNames for Client<Mapping> {
+    fn player_name(&self, slot: i64) -> Option<String> {
+        self.players()
+            .find(|player| {
+                player.team() == self.this_player().team() && player.slot() as i64 == slot
+            })
+            .map(|player| player.alias().to_string())
+    }
+
+    fn item_name(&self, receiver: i64, item: i64) -> Option<String> {
+        self.players()
+            .find(|player| {
+                player.team() == self.this_player().team() && player.slot() as i64 == receiver
+            })
+            .and_then(|player| player.game().item(item))
+            .map(|item| item.name().to_string())
+    }
+
+    fn location_name(&self, finder: i64, location: i64) -> Option<String> {
+        self.players()
+            .find(|player| {
+                player.team() == self.this_player().team() && player.slot() as i64 == finder
+            })
+            .and_then(|player| player.game().location(location))
+            .map(|location| location.name().to_string())
+    }
+}
+
+/// Hints are stored as a list of Hint objects (see the network protocol), which only have ids in them
+fn parse_hints<N: HintNames>(names: &N, value: Value) -> Vec<HintEntry> {
+    let Value::Array(hints) = value else {
+        return vec![];
+    };
+    hints
+        .iter()
+        .filter_map(|hint| {
+            let location = hint.get("location")?.as_i64()?;
+            let item = hint.get("item")?.as_i64()?;
+            let finder = hint.get("finding_player")?.as_i64()?;
+            let receiver = hint.get("receiving_player")?.as_i64()?;
+            Some(HintEntry {
+                location: names
+                    .location_name(finder, location)
+                    .unwrap_or_else(|| format!("Location #{}", location)),
+                item: names
+                    .item_name(receiver, item)
+                    .unwrap_or_else(|| format!("Item #{}", item)),
+                finder: names
+                    .player_name(finder)
+                    .unwrap_or_else(|| format!("Player {}", finder)),
+                receiver: names
+                    .player_name(receiver)
+                    .unwrap_or_else(|| format!("Player {}", receiver)),
+                found: hint.get("found").and_then(Value::as_bool).unwrap_or(false),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Two players: Dante (slot 1) playing DMC1 and Vergil (slot 2) playing something else
+    struct Names {
+        players: HashMap<i64, &'static str>,
+        items: HashMap<(i64, i64), &'static str>,
+        locations: HashMap<(i64, i64), &'static str>,
+    }
+
+    impl HintNames for Names {
+        fn player_name(&self, slot: i64) -> Option<String> {
+            self.players.get(&slot).map(|name| name.to_string())
+        }
+
+        fn item_name(&self, receiver: i64, item: i64) -> Option<String> {
+            self.items
+                .get(&(receiver, item))
+                .map(|name| name.to_string())
+        }
+
+        fn location_name(&self, finder: i64, location: i64) -> Option<String> {
+            self.locations
+                .get(&(finder, location))
+                .map(|name| name.to_string())
+        }
+    }
+
+    fn names() -> Names {
+        Names {
+            players: HashMap::from([(1, "Dante"), (2, "Vergil")]),
+            items: HashMap::from([((1, 6), "Blue Orb"), ((2, 6), "Yamato")]),
+            locations: HashMap::from([
+                ((1, 1000), "Mission #1 Complete"),
+                ((2, 1000), "Temen-ni-gru"),
+            ]),
+        }
+    }
+
+    fn hint(location: i64, item: i64, finder: i64, receiver: i64, found: bool) -> Value {
+        json!({
+            "receiving_player": receiver,
+            "finding_player": finder,
+            "location": location,
+            "item": item,
+            "found": found,
+            "entrance": "",
+            "item_flags": 0,
+            "status": 0,
+            "class": "Hint",
+        })
+    }
+
+    #[test]
+    fn hints_use_each_players_game_for_names() {
+        let hints = parse_hints(
+            &names(),
+            json!([hint(1000, 6, 2, 1, false), hint(1000, 6, 1, 2, true)]),
+        );
+        assert_eq!(hints.len(), 2);
+        // Vergil has Dante's Blue Orb
+        assert_eq!(hints[0].location, "Temen-ni-gru");
+        assert_eq!(hints[0].item, "Blue Orb");
+        assert_eq!(hints[0].finder, "Vergil");
+        assert_eq!(hints[0].receiver, "Dante");
+        assert!(!hints[0].found);
+        // Dante has Vergil's Yamato
+        assert_eq!(hints[1].location, "Mission #1 Complete");
+        assert_eq!(hints[1].item, "Yamato");
+        assert_eq!(hints[1].finder, "Dante");
+        assert_eq!(hints[1].receiver, "Vergil");
+        assert!(hints[1].found);
+    }
+
+    #[test]
+    fn unknown_ids_are_still_shown() {
+        let hints = parse_hints(&names(), json!([hint(55, 77, 3, 1, false)]));
+        assert_eq!(hints[0].location, "Location #55");
+        assert_eq!(hints[0].item, "Item #77");
+        assert_eq!(hints[0].finder, "Player 3");
+        assert_eq!(hints[0].receiver, "Dante");
+    }
+
+    #[test]
+    fn malformed_hints_are_skipped() {
+        assert!(parse_hints(&names(), json!(null)).is_empty());
+        let hints = parse_hints(
+            &names(),
+            json!([{"location": 1000}, hint(1000, 6, 1, 1, false)]),
+        );
+        assert_eq!(hints.len(), 1);
+    }
+
+    fn progress(deaths: u32, completed: &[u8], ranks: &[(u8, Rank)]) -> SlotProgress {
+        SlotProgress {
+            mission: 0,
+            deaths,
+            missions_completed: completed.to_vec(),
+            ranks: ranks.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn merge_keeps_the_most_deaths() {
+        let mut local = progress(3, &[], &[]);
+        local.merge(progress(7, &[], &[]));
+        assert_eq!(local.deaths, 7);
+        local.merge(progress(2, &[], &[]));
+        assert_eq!(local.deaths, 7);
+    }
+
+    #[test]
+    fn merge_unions_completed_missions() {
+        let mut local = progress(0, &[1, 4], &[]);
+        local.merge(progress(0, &[2, 4, 3], &[]));
+        assert_eq!(local.missions_completed, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn merge_keeps_the_best_rank() {
+        let mut local = progress(0, &[], &[(1, Rank::A), (2, Rank::C)]);
+        local.merge(progress(
+            0,
+            &[],
+            &[(1, Rank::D), (2, Rank::S), (3, Rank::B)],
+        ));
+        // A worse remote rank doesn't overwrite a better local one
+        assert_eq!(local.ranks[&1], Rank::A);
+        assert_eq!(local.ranks[&2], Rank::S);
+        assert_eq!(local.ranks[&3], Rank::B);
+    }
+
+    #[test]
+    fn remote_progress_keeps_the_current_mission() {
+        let mut local = progress(1, &[1], &[(1, Rank::S)]);
+        local.mission = 5;
+        let mut remote = progress(4, &[1, 2], &[(1, Rank::C), (2, Rank::A)]);
+        remote.mission = 2;
+        local.merge_remote(remote);
+        assert_eq!(local.mission, 5);
+        assert_eq!(local.deaths, 4);
+        assert_eq!(local.missions_completed, vec![1, 2]);
+        assert_eq!(local.ranks[&1], Rank::S);
+        assert_eq!(local.ranks[&2], Rank::A);
+    }
+
+    #[test]
+    fn remote_progress_round_trips_through_json() {
+        let remote = progress(2, &[1, 3], &[(3, Rank::B)]);
+        let value = serde_json::to_value(&remote).unwrap();
+        assert_eq!(
+            serde_json::from_value::<SlotProgress>(value).unwrap(),
+            remote
+        );
+    }
+}
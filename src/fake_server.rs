@@ -0,0 +1,391 @@
+//! Stand-in for an Archipelago MultiServer, just enough of the protocol for the client to connect,
+//! receive items, check locations and send DeathLinks against it in tests
+
+use crate::constants::GAME_NAME;
+use crate::data::generated_locations;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+/// The player the client connects as
+pub(crate) const SLOT: i64 = 1;
+const TEAM: i64 = 0;
+/// Location IDs are handed out from here, in name order
+const LOCATION_BASE: i64 = 1000;
+
+/// Items the fake data package knows about. Only needs what the tests send
+const ITEMS: [(&str, i64); 8] = [
+    ("Shotgun", 2),
+    ("Blue Orb", 6),
+    ("Purple Orb", 7),
+    ("Vital Star", 12),
+    ("Yellow Orb", 15),
+    ("Red Orb - 100", 41),
+    ("Red Orb - 150", 42),
+    ("Red Orb - 200", 43),
+];
+/// What every location holds when scouted
+const PLACED_ITEM: i64 = 41;
+
+/// Everything the client has sent that a test might want to look at
+#[derive(Debug, Default)]
+pub(crate) struct ServerLog {
+    /// Successful Connect packets
+    pub(crate) connects: usize,
+    /// Sync packets, each asks for every received item again
+    pub(crate) syncs: usize,
+    pub(crate) checked_locations: Vec<i64>,
+    pub(crate) statuses: Vec<i64>,
+    /// Data from Bounce packets tagged DeathLink
+    pub(crate) death_links: Vec<Value>,
+}
+
+enum ServerCommand {
+    SendItems(Vec<i64>),
+    DeathLink { source: String, cause: String },
+    Disconnect,
+}
+
+/// State that outlives a single connection, like a real room
+struct Room {
+    slot_name: String,
+    slot_data: Value,
+    locations: HashMap<String, i64>,
+    /// Item IDs sent to the slot, in order
+    received: Vec<i64>,
+    data_storage: HashMap<String, Value>,
+    log: Arc<Mutex<ServerLog>>,
+}
+
+pub(crate) struct FakeServer {
+    pub(crate) url: String,
+    pub(crate) log: Arc<Mutex<ServerLog>>,
+    locations: HashMap<String, i64>,
+    commands: Sender<ServerCommand>,
+}
+
+impl FakeServer {
+    /// Start a server on a free local port, for a single slot with the given slot data
+    pub(crate) fn start(slot_name: &str, slot_data: Value) -> FakeServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind fake server");
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let mut names: Vec<&str> = generated_locations::ITEM_MISSION_MAP
+            .keys()
+            .copied()
+            .collect();
+        names.sort();
+        let locations: HashMap<String, i64> = names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), LOCATION_BASE + i as i64))
+            .collect();
+        let log = Arc::new(Mutex::new(ServerLog::default()));
+        let (commands, receiver) = mpsc::channel();
+        let room = Room {
+            slot_name: slot_name.to_string(),
+            slot_data,
+            locations: locations.clone(),
+            received: vec![],
+            data_storage: HashMap::new(),
+            log: log.clone(),
+        };
+        thread::spawn(move || serve(listener, room, receiver));
+        FakeServer {
+            url,
+            log,
+            locations,
+            commands,
+        }
+    }
+
+    pub(crate) fn location_id(&self, name: &str) -> i64 {
+        self.locations[name]
+    }
+
+    /// Send items to the slot, as if someone found them
+    pub(crate) fn send_items(&self, items: Vec<i64>) {
+        self.commands.send(ServerCommand::SendItems(items)).unwrap();
+    }
+
+    /// Someone else died
+    pub(crate) fn death_link(&self, source: &str, cause: &str) {
+        self.commands
+            .send(ServerCommand::DeathLink {
+                source: source.to_string(),
+                cause: cause.to_string(),
+            })
+            .unwrap();
+    }
+
+    /// Drop the current connection, the next one is accepted as normal
+    pub(crate) fn disconnect(&self) {
+        self.commands.send(ServerCommand::Disconnect).unwrap();
+    }
+}
+
+fn serve(listener: TcpListener, mut room: Room, commands: Receiver<ServerCommand>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let Ok(mut socket) = tungstenite::accept(stream) else {
+            continue;
+        };
+        // Short timeout so commands from the test get picked up between messages
+        let _ = socket
+            .get_ref()
+            .set_read_timeout(Some(Duration::from_millis(10)));
+        if !room.handle_connection(&mut socket, &commands) {
+            return;
+        }
+    }
+}
+
+impl Room {
+    /// Runs until the client goes away or the test asks for a disconnect. False once the test is
+    /// done with the server
+    fn handle_connection(
+        &mut self,
+        socket: &mut WebSocket<TcpStream>,
+        commands: &Receiver<ServerCommand>,
+    ) -> bool {
+        send(socket, vec![self.room_info()]);
+        loop {
+            match commands.try_recv() {
+                Ok(ServerCommand::SendItems(items)) => {
+                    let index = self.received.len();
+                    self.received.extend(items);
+                    send(socket, vec![self.received_items(index)]);
+                }
+                Ok(ServerCommand::DeathLink { source, cause }) => {
+                    send(
+                        socket,
+                        vec![json!({
+                            "cmd": "Bounced",
+                            "tags": ["DeathLink"],
+                            "data": {"time": 0.0, "source": source, "cause": cause},
+                        })],
+                    );
+                }
+                Ok(ServerCommand::Disconnect) => {
+                    let _ = socket.get_mut().shutdown(Shutdown::Both);
+                    return true;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => return false,
+            }
+            match socket.read() {
+                Ok(Message::Text(text)) => {
+                    let Ok(Value::Array(packets)) = serde_json::from_str::<Value>(&text) else {
+                        continue;
+                    };
+                    for packet in packets {
+                        let replies = self.handle_packet(&packet);
+                        if !replies.is_empty() {
+                            send(socket, replies);
+                        }
+                    }
+                }
+                Ok(Message::Close(_)) => return true,
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err))
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => return true,
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &Value) -> Vec<Value> {
+        match packet["cmd"].as_str().unwrap_or_default() {
+            "GetDataPackage" => vec![self.data_package()],
+            "Connect" => {
+                if packet["name"].as_str() != Some(self.slot_name.as_str()) {
+                    return vec![json!({"cmd": "ConnectionRefused", "errors": ["InvalidSlot"]})];
+                }
+                self.log.lock().unwrap().connects += 1;
+                vec![self.connected(), self.received_items(0)]
+            }
+            "Sync" => {
+                self.log.lock().unwrap().syncs += 1;
+                vec![self.received_items(0)]
+            }
+            "LocationChecks" => {
+                let mut log = self.log.lock().unwrap();
+                for location in ids(&packet["locations"]) {
+                    if !log.checked_locations.contains(&location) {
+                        log.checked_locations.push(location);
+                    }
+                }
+                vec![json!({"cmd": "RoomUpdate", "checked_locations": log.checked_locations})]
+            }
+            "LocationScouts" => {
+                let locations: Vec<Value> = ids(&packet["locations"])
+                    .into_iter()
+                    .map(|location| network_item(PLACED_ITEM, location, SLOT))
+                    .collect();
+                vec![json!({"cmd": "LocationInfo", "locations": locations})]
+            }
+            "StatusUpdate" => {
+                if let Some(status) = packet["status"].as_i64() {
+                    self.log.lock().unwrap().statuses.push(status);
+                }
+                vec![]
+            }
+            "Bounce" => {
+                let tags = packet["tags"].as_array().cloned().unwrap_or_default();
+                if tags.iter().any(|tag| tag == "DeathLink") {
+                    self.log
+                        .lock()
+                        .unwrap()
+                        .death_links
+                        .push(packet["data"].clone());
+                }
+                // The real server bounces back to everyone with the tag, sender included
+                vec![json!({"cmd": "Bounced", "tags": tags, "data": packet["data"]})]
+            }
+            "Get" => {
+                let keys: serde_json::Map<String, Value> = packet["keys"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(|key| {
+                        let value = self.data_storage.get(key).cloned().unwrap_or(Value::Null);
+                        (key.to_string(), value)
+                    })
+                    .collect();
+                vec![json!({"cmd": "Retrieved", "keys": keys})]
+            }
+            "Set" => {
+                let key = packet["key"].as_str().unwrap_or_default().to_string();
+                let original = self.data_storage.get(&key).cloned().unwrap_or(Value::Null);
+                // Only "replace" matters for what the client sets
+                let value = packet["operations"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|operation| operation["operation"] == "replace")
+                    .map(|operation| operation["value"].clone())
+                    .next_back()
+                    .unwrap_or(original.clone());
+                self.data_storage.insert(key.clone(), value.clone());
+                if packet["want_reply"].as_bool().unwrap_or(false) {
+                    vec![json!({
+                        "cmd": "SetReply",
+                        "key": key,
+                        "value": value,
+                        "original_value": original,
+                        "slot": SLOT,
+                    })]
+                } else {
+                    vec![]
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    fn room_info(&self) -> Value {
+        let version = json!({"major": 0, "minor": 6, "build": 2, "class": "Version"});
+        json!({
+            "cmd": "RoomInfo",
+            "version": version,
+            "generator_version": version,
+            "tags": ["AP"],
+            "password": false,
+            "permissions": {"release": 2, "collect": 2, "remaining": 2},
+            "hint_cost": 10,
+            "location_check_points": 1,
+            "games": [GAME_NAME],
+            "datapackage_checksums": {GAME_NAME: "fake-dmc1", "Archipelago": "fake-archipelago"},
+            "seed_name": "fake",
+            "time": 0.0,
+        })
+    }
+
+    fn data_package(&self) -> Value {
+        let items: HashMap<&str, i64> = ITEMS.into_iter().collect();
+        json!({
+            "cmd": "DataPackage",
+            "data": {"games": {
+                GAME_NAME: {
+                    "item_name_to_id": items,
+                    "location_name_to_id": self.locations,
+                    "checksum": "fake-dmc1",
+                },
+                "Archipelago": {
+                    "item_name_to_id": {"Nothing": -1},
+                    "location_name_to_id": {"Cheat Console": -1, "Server": -2},
+                    "checksum": "fake-archipelago",
+                },
+            }},
+        })
+    }
+
+    fn connected(&self) -> Value {
+        let checked = self.log.lock().unwrap().checked_locations.clone();
+        let missing: Vec<i64> = self
+            .locations
+            .values()
+            .copied()
+            .filter(|location| !checked.contains(location))
+            .collect();
+        json!({
+            "cmd": "Connected",
+            "team": TEAM,
+            "slot": SLOT,
+            "players": [{
+                "team": TEAM,
+                "slot": SLOT,
+                "alias": self.slot_name,
+                "name": self.slot_name,
+                "class": "NetworkPlayer",
+            }],
+            "missing_locations": missing,
+            "checked_locations": checked,
+            "slot_data": self.slot_data,
+            "slot_info": {SLOT.to_string(): {
+                "name": self.slot_name,
+                "game": GAME_NAME,
+                "type": 1,
+                "group_members": [],
+                "class": "NetworkSlot",
+            }},
+            "hint_points": 0,
+        })
+    }
+
+    /// Everything from `index` on
+    fn received_items(&self, index: usize) -> Value {
+        let items: Vec<Value> = self.received[index..]
+            .iter()
+            .enumerate()
+            // As if the slot found them all in its own world
+            .map(|(i, item)| network_item(*item, LOCATION_BASE + (index + i) as i64, SLOT))
+            .collect();
+        json!({"cmd": "ReceivedItems", "index": index, "items": items})
+    }
+}
+
+fn network_item(item: i64, location: i64, player: i64) -> Value {
+    json!({"item": item, "location": location, "player": player, "flags": 0, "class": "NetworkItem"})
+}
+
+fn ids(value: &Value) -> Vec<i64> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_i64)
+        .collect()
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, packets: Vec<Value>) {
+    let _ = socket.send(Message::text(Value::Array(packets).to_string()));
+}
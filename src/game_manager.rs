@@ -1,12 +1,21 @@
-use crate::constants::Difficulty;
-use crate::mapping::MAPPING;
+use crate::archipelago::TX_DEATHLINK;
+use crate::config::HurtLink;
+use crate::constants::{Difficulty, GUN_MAP, MELEE_MAP};
+use crate::game_memory::{DMC1Memory, GameMemory};
+use crate::mapping::{DeathlinkSetting, MAPPING};
+use crate::ui::overlay;
+use crate::ui::overlay::{MessageSegment, MessageType, OverlayMessage};
 use crate::utilities::DMC1_ADDRESS;
+use crate::{data_storage, utilities};
+use bimap::BiMap;
+use randomizer_utilities::archipelago_utilities::DeathLinkData;
 use randomizer_utilities::read_data_from_address;
-use std::collections::HashSet;
+use randomizer_utilities::ui::font_handler::{RED, WHITE};
+use std::collections::{HashSet, VecDeque};
 use std::fmt::{Display, Formatter};
 use std::mem::transmute;
-use std::ptr::write;
-use std::sync::{LazyLock, RwLock, RwLockWriteGuard};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Default)]
 pub(crate) struct ArchipelagoData {
@@ -153,6 +162,64 @@ pub struct SessionData {
     orb_flags: u32,
 }
 
+impl SessionData {
+    /// All zero session, only useful for standing in for the game's memory
+    #[cfg(test)]
+    pub(crate) fn zeroed() -> Box<Self> {
+        // Safety: Every field is a plain integer (or bool), so all zeroes is valid
+        Box::new(unsafe { std::mem::zeroed() })
+    }
+
+    /// Sets the count of an existing item, or adds it to the end of the inventory.
+    /// False if it had to be added and the inventory is full
+    pub(crate) fn insert_unique_item(&mut self, item_data: &ItemData) -> bool {
+        for i in 0..self.item_count {
+            let item = &mut self.item_data[i as usize];
+            if item == item_data {
+                item.count = item_data.count;
+                return true;
+            }
+        }
+        self.push_item(item_data)
+    }
+
+    /// Adds onto the count of an existing item, or adds it to the end of the inventory.
+    /// False if it had to be added and the inventory is full
+    pub(crate) fn insert_item(&mut self, item_data: &ItemData) -> bool {
+        for i in 0..self.item_count {
+            let item = &mut self.item_data[i as usize];
+            if item == item_data {
+                item.count += item_data.count;
+                return true;
+            }
+        }
+        self.push_item(item_data)
+    }
+
+    fn push_item(&mut self, item_data: &ItemData) -> bool {
+        let Some(item) = self.item_data.get_mut(self.item_count as usize) else {
+            return false;
+        };
+        item.category = item_data.category;
+        item.id = item_data.id;
+        item.count = item_data.count;
+        self.item_count += 1;
+        true
+    }
+
+    pub(crate) fn clear_item(&mut self, item_data: &ItemData) {
+        for i in 0..self.item_count {
+            let item = &mut self.item_data[i as usize];
+            if item == item_data {
+                // Swap in last element
+                self.item_data[i as usize] = self.item_data[(self.item_count - 1) as usize];
+                self.item_count -= 1;
+                return;
+            }
+        }
+    }
+}
+
 /// Error type for session access
 #[derive(Debug)]
 pub enum SessionError {
@@ -206,11 +273,6 @@ pub fn get_mission() -> u8 {
     with_session_read(|s| s.mission).unwrap()
 }
 
-/// Get current difficulty
-pub fn get_difficulty() -> Difficulty {
-    Difficulty::from_repr(with_session_read(|s| s.difficulty).unwrap() as usize).unwrap()
-}
-
 // Player data
 
 #[repr(C)]
@@ -249,6 +311,18 @@ pub struct PlayerData {
     charge_timer: [i16; 2],
 }
 
+impl PlayerData {
+    /// All zero player data, only useful for standing in for the game's memory
+    #[cfg(test)]
+    pub(crate) fn zeroed() -> Box<Self> {
+        // Safety: Every field is a plain integer or float, so all zeroes is valid
+        Box::new(unsafe { std::mem::zeroed() })
+    }
+}
+
+/// Where an actor's world position is, every actor (pickups included) starts out laid out like Dante's
+pub(crate) const ACTOR_POSITION_OFFSET: usize = std::mem::offset_of!(PlayerData, position);
+
 /// Error type for player data access
 #[derive(Debug)]
 pub enum PlayerDataError {
@@ -304,11 +378,12 @@ fn player_data_valid(_s: &PlayerData) -> bool {
 #[repr(C)]
 pub struct EventData {
     unknown: [u8; 624],
-    track: u32,
-    room: u32,
+    pub(crate) track: u32,
+    pub(crate) room: u32,
     unknown1: [u8; 16],
-    next_track: u32,
-    next_room: u32,
+    /// Where the game goes once the current room/mission is done
+    pub(crate) next_track: u32,
+    pub(crate) next_room: u32,
 }
 
 const EVENT_DATA: usize = 0x60B148;
@@ -329,6 +404,20 @@ where
     }
 }
 
+pub fn with_event_data<F, R>(f: F) -> Result<R, PlayerDataError>
+where
+    F: FnOnce(&mut EventData) -> R,
+{
+    let addr = *EVENT_DATA_PTR;
+    unsafe {
+        let s = &mut *(read_data_from_address::<*mut EventData>(addr));
+        if !event_data_valid(s) {
+            return Err(PlayerDataError::NotUsable);
+        }
+        Ok(f(s))
+    }
+}
+
 fn event_data_valid(_s: &EventData) -> bool {
     if *EVENT_DATA_PTR != 0 {
         return true;
@@ -352,20 +441,15 @@ pub fn get_track() -> i32 {
     with_event_data_read(|s| s.track).unwrap() as i32
 }
 
-pub(crate) fn give_hp(blue_orb_count: i32) {
-    if let Err(e) = with_session(|s| {
+pub(crate) fn give_hp<M: GameMemory>(memory: &M, blue_orb_count: i32) {
+    if let Err(e) = memory.with_session(|s| {
         s.hp += blue_orb_count as u8;
     }) {
         log::error!("Failed to give hp: {:?}", e);
     }
-    let something_hp = read_data_from_address::<usize>(*DMC1_ADDRESS + 0x60b0d8) + 0x98;
-    unsafe {
-        write(
-            something_hp as *mut u8,
-            read_data_from_address::<u8>(something_hp) + blue_orb_count as u8,
-        );
-    }
-    if let Err(e) = with_active_player_data(|d| {
+    let (hp, magic) = memory.cached_stats();
+    memory.set_cached_stats(hp + blue_orb_count as u8, magic);
+    if let Err(e) = memory.with_player_data(|d| {
         d.hp += blue_orb_count as u16 * 100;
         d.max_hp += blue_orb_count as u16 * 100;
     }) {
@@ -373,21 +457,16 @@ pub(crate) fn give_hp(blue_orb_count: i32) {
     }
 }
 
-pub(crate) fn give_magic(purple_orb_count: i32, data: &RwLockWriteGuard<ArchipelagoData>) {
+pub(crate) fn give_magic<M: GameMemory>(memory: &M, purple_orb_count: i32) {
     // TODO Check if I have DT?
-    if let Err(e) = with_session(|s| {
+    if let Err(e) = memory.with_session(|s| {
         s.magic += purple_orb_count as u8;
     }) {
         log::error!("Failed to give magic: {:?}", e);
     }
-    let something_magic = read_data_from_address::<usize>(*DMC1_ADDRESS + 0x60b0d8) + 0xA3;
-    unsafe {
-        write(
-            something_magic as *mut u8,
-            read_data_from_address::<u8>(something_magic) + purple_orb_count as u8,
-        );
-    }
-    if let Err(e) = with_active_player_data(|d| {
+    let (hp, magic) = memory.cached_stats();
+    memory.set_cached_stats(hp, magic + purple_orb_count as u8);
+    if let Err(e) = memory.with_player_data(|d| {
         d.magic_human += purple_orb_count as u16 * 120;
         d.max_magic_human += purple_orb_count as u16 * 120;
         d.magic_demon += purple_orb_count as u16 * 200;
@@ -397,24 +476,166 @@ pub(crate) fn give_magic(purple_orb_count: i32, data: &RwLockWriteGuard<Archipel
     }
 }
 
-pub(crate) fn hurt_dante() {
-    let damage_fraction: u16 = match get_difficulty() {
-        Difficulty::Easy => (1.0 / 4.0) as u16,
-        Difficulty::Normal => (1.0 / 3.0) as u16,
-        Difficulty::Hard => (1.0 / 2.0) as u16,
-        Difficulty::DanteMustDie => (5.0 / 6.0) as u16,
+/// Damage from a received HurtLink, the difficulty's percentage of max HP plus any flat damage
+pub(crate) fn hurt_link_damage(max_hp: u16, difficulty: Difficulty, settings: &HurtLink) -> u16 {
+    let percent = settings.percent_for(difficulty).clamp(0.0, 100.0);
+    let damage = (max_hp as f32 * percent / 100.0).round() as u16;
+    damage.saturating_add(settings.flat_damage)
+}
+
+pub(crate) fn hurt_dante<M: GameMemory>(memory: &M, settings: &HurtLink) {
+    let difficulty = match memory.with_session(|s| Difficulty::from_repr(s.difficulty as usize)) {
+        Ok(Some(difficulty)) => difficulty,
+        Ok(None) => {
+            log::error!("Unknown difficulty, not applying HurtLink");
+            return;
+        }
+        Err(e) => {
+            log::error!("Failed to get difficulty for HurtLink: {:?}", e);
+            return;
+        }
     };
-    with_active_player_data(|d| {
-        d.hp = u16::max(d.hp - (d.max_hp * damage_fraction), 0);
-    })
-    .unwrap();
+    if let Err(e) = memory.with_player_data(|d| {
+        let damage = hurt_link_damage(d.max_hp, difficulty, settings);
+        // An already dead Dante stays dead
+        let floor = if settings.never_lethal {
+            d.hp.min(1)
+        } else {
+            0
+        };
+        let hp = d.hp.saturating_sub(damage).max(floor);
+        if d.hp > 0 && hp == 0 {
+            DEATH_WATCH.lock().unwrap().mark_link_kill(Instant::now());
+        }
+        d.hp = hp;
+    }) {
+        log::error!("Failed to hurt Dante: {:?}", e);
+    }
 }
 
-pub(crate) fn kill_dante() {
-    with_active_player_data(|d| {
+pub(crate) fn kill_dante<M: GameMemory>(memory: &M) {
+    if let Err(e) = memory.with_player_data(|d| {
+        if d.hp > 0 {
+            DEATH_WATCH.lock().unwrap().mark_link_kill(Instant::now());
+        }
         d.hp = 0;
-    })
-    .unwrap();
+    }) {
+        log::error!("Failed to kill Dante: {:?}", e);
+    }
+}
+
+/// How long a kill from a received link is waited on before it's forgotten
+const LINK_KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Death {
+    /// Dante died on his own, worth sending a DeathLink for
+    Own,
+    /// A received DeathLink/HurtLink did the killing
+    FromLink,
+}
+
+/// Tracks Dante's HP across frames to catch the frame he dies
+pub(crate) struct DeathWatch {
+    /// HP Dante had last frame
+    last_hp: u16,
+    /// Room seen last frame, -1 if there wasn't one
+    last_room: i32,
+    /// When a received link took Dante to 0 HP, so that death doesn't get sent back out
+    link_kill: Option<Instant>,
+}
+
+impl DeathWatch {
+    pub(crate) const fn new() -> Self {
+        Self {
+            last_hp: 0,
+            last_room: -1,
+            link_kill: None,
+        }
+    }
+
+    pub(crate) fn mark_link_kill(&mut self, now: Instant) {
+        self.link_kill = Some(now);
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Run every frame, reports the frame where Dante's HP drops to 0
+    pub(crate) fn update<M: GameMemory>(&mut self, memory: &M, now: Instant) -> Option<Death> {
+        if self
+            .link_kill
+            .is_some_and(|at| now.duration_since(at) > LINK_KILL_TIMEOUT)
+        {
+            self.link_kill = None;
+        }
+        if memory.with_session(|_| ()).is_err() || memory.on_main_menu() {
+            self.reset();
+            return None;
+        }
+        let (hp, max_hp) = match memory.with_player_data(|d| (d.hp, d.max_hp)) {
+            Ok(stats) => stats,
+            Err(_) => {
+                self.last_hp = 0;
+                return None;
+            }
+        };
+        // The actor gets rebuilt on room changes and what it holds in the meantime isn't known, so
+        // the first frame of a room and an actor without max HP only set up the next comparison
+        let room = memory.room();
+        if room != self.last_room || max_hp == 0 {
+            self.last_room = room;
+            self.last_hp = if max_hp == 0 { 0 } else { hp };
+            return None;
+        }
+        // Only the change from alive to dead counts, sitting on the game over screen isn't another death
+        let was_alive = std::mem::replace(&mut self.last_hp, hp) > 0;
+        if !was_alive || hp != 0 {
+            return None;
+        }
+        Some(match self.link_kill.take() {
+            Some(_) => Death::FromLink,
+            None => Death::Own,
+        })
+    }
+}
+
+static DEATH_WATCH: Mutex<DeathWatch> = Mutex::new(DeathWatch::new());
+
+/// Run every frame, sends out a DeathLink when Dante's HP drops to 0
+pub(crate) fn check_for_death() {
+    let Some(death) = DEATH_WATCH
+        .lock()
+        .unwrap()
+        .update(&DMC1Memory, Instant::now())
+    else {
+        return;
+    };
+    data_storage::record_death();
+    if death == Death::FromLink {
+        log::debug!("Dante was killed by a DeathLink, not sending one back");
+        return;
+    }
+    let sends_deaths = MAPPING.read().is_ok_and(|mapping| {
+        mapping
+            .as_ref()
+            .is_some_and(|m| !matches!(m.death_link, DeathlinkSetting::Off))
+    });
+    if !sends_deaths {
+        return;
+    }
+    let cause = format!(
+        "Dante fell in Mission {} (Room {})",
+        DMC1Memory.mission(),
+        DMC1Memory.room()
+    );
+    log::info!("Sending DeathLink: {}", cause);
+    if let Some(tx) = TX_DEATHLINK.get()
+        && let Err(err) = tx.send(DeathLinkData { cause })
+    {
+        log::error!("Failed to send DeathLink: {}", err);
+    }
 }
 
 // Most likely un-needed, but may as well keep around
@@ -431,9 +652,563 @@ pub static CHANGE_EQUIPPED_MELEE: LazyLock<extern "C" fn(u32, u32)> = LazyLock::
     transmute::<usize, extern "C" fn(u32, u32)>(*DMC1_ADDRESS + 0x2C99C0)
 });
 
-pub(crate) fn give_red_orbs(orbs: u32) {
-    with_session(|s| {
+pub(crate) fn give_red_orbs<M: GameMemory>(memory: &M, orbs: u32) {
+    if let Err(e) = memory.with_session(|s| {
         s.red_orbs += orbs;
-    })
-    .unwrap();
+    }) {
+        log::error!("Failed to give red orbs: {:?}", e);
+    }
+}
+
+// Traps
+
+#[derive(Debug, Copy, Clone, PartialEq, strum_macros::Display)]
+pub(crate) enum Trap {
+    #[strum(to_string = "HP Drain")]
+    HpDrain,
+    #[strum(to_string = "Magic Drain")]
+    MagicDrain,
+    #[strum(to_string = "Weapon Swap")]
+    WeaponSwap,
+    #[strum(to_string = "Red Orb Tax")]
+    RedOrbTax,
+    #[strum(to_string = "Input Lock")]
+    InputLock,
+}
+
+const TRAPS: [Trap; 5] = [
+    Trap::HpDrain,
+    Trap::MagicDrain,
+    Trap::WeaponSwap,
+    Trap::RedOrbTax,
+    Trap::InputLock,
+];
+
+impl Trap {
+    /// Traps go by item name, with or without " Trap" on the end, as their IDs are up to the data package
+    pub(crate) fn from_item_name(name: &str) -> Option<Trap> {
+        let name = name.strip_suffix(" Trap").unwrap_or(name);
+        TRAPS.into_iter().find(|trap| trap.to_string() == name)
+    }
+}
+
+/// Fraction of max HP taken by an HP drain, never goes below 1 HP
+const HP_DRAIN_FRACTION: u16 = 4;
+/// Percentage of red orbs taken by the tax
+const RED_ORB_TAX_PERCENT: u32 = 10;
+const INPUT_LOCK_DURATION: Duration = Duration::from_secs(5);
+
+static TRAP_QUEUE: LazyLock<Mutex<VecDeque<Trap>>> = LazyLock::new(|| Mutex::new(VecDeque::new()));
+static INPUT_LOCKED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Traps wait in the queue until Dante can actually be messed with
+pub(crate) fn queue_trap(trap: Trap) {
+    log::debug!("Queued trap: {}", trap);
+    match TRAP_QUEUE.lock() {
+        Ok(mut queue) => queue.push_back(trap),
+        Err(err) => log::error!("Failed to queue trap: {}", err),
+    }
+}
+
+/// How long after a room change before traps can go off, Dante is still being set up for a bit
+const ROOM_SETTLE: Duration = Duration::from_secs(2);
+/// Frames in a row Dante's actor can sit perfectly still before the game counts as paused
+const FROZEN_FRAMES: u32 = 10;
+
+/// The parts of Dante's actor that change every frame while the game runs, the idle timer keeps
+/// counting when he's standing still
+#[derive(Copy, Clone, PartialEq)]
+struct ActorMotion {
+    state: [u8; 8],
+    position: [f32; 4],
+    rotation: f32,
+    idle_timer: u16,
+    charge_timer: [i16; 2],
+}
+
+impl ActorMotion {
+    fn of(player: &PlayerData) -> Self {
+        let position = &player.position;
+        ActorMotion {
+            state: player.state,
+            position: [position.x, position.y, position.z, position.a],
+            rotation: player.rotation,
+            idle_timer: player.idle_timer,
+            charge_timer: player.charge_timer,
+        }
+    }
+}
+
+/// Decides when Dante can be messed with. There's no known flag for the pause menu or a room load,
+/// so this goes off what can be read:
+/// - The main menu and the shop have their own screens
+/// - The game over screen has Dante lying at 0 HP
+/// - A room load changes the room and rebuilds the actor (0 max HP), traps wait [ROOM_SETTLE] after one
+/// - Pausing freezes the actor, so [FROZEN_FRAMES] without any change counts as paused. Cutscenes
+///   stop him the same way
+pub(crate) struct TrapGate {
+    /// (room, track)
+    room: Option<(i32, i32)>,
+    room_entered: Option<Instant>,
+    last_motion: Option<ActorMotion>,
+    still_frames: u32,
+}
+
+impl TrapGate {
+    pub(crate) const fn new() -> Self {
+        TrapGate {
+            room: None,
+            room_entered: None,
+            last_motion: None,
+            still_frames: 0,
+        }
+    }
+
+    /// Run every frame, true if Dante is controllable
+    pub(crate) fn update<M: GameMemory>(&mut self, memory: &M, now: Instant) -> bool {
+        if memory.on_main_menu() || memory.in_shop() || memory.with_session(|_| ()).is_err() {
+            *self = TrapGate::new();
+            return false;
+        }
+        let Ok((hp, max_hp, motion)) =
+            memory.with_player_data(|d| (d.hp, d.max_hp, ActorMotion::of(d)))
+        else {
+            *self = TrapGate::new();
+            return false;
+        };
+        let room = (memory.room(), memory.track());
+        if self.room != Some(room) || max_hp == 0 {
+            self.room = Some(room);
+            self.room_entered = Some(now);
+            self.last_motion = None;
+            self.still_frames = 0;
+            return false;
+        }
+        if self.last_motion.replace(motion) == Some(motion) {
+            self.still_frames = self.still_frames.saturating_add(1);
+        } else {
+            self.still_frames = 0;
+        }
+        hp > 0
+            && self.still_frames < FROZEN_FRAMES
+            && self
+                .room_entered
+                .is_some_and(|entered| now.duration_since(entered) >= ROOM_SETTLE)
+    }
+}
+
+static TRAP_GATE: Mutex<TrapGate> = Mutex::new(TrapGate::new());
+
+/// Run every frame, sets off one queued trap at a time and keeps an input lock going
+pub(crate) fn process_traps() {
+    let controllable = TRAP_GATE
+        .lock()
+        .is_ok_and(|mut gate| gate.update(&DMC1Memory, Instant::now()));
+    if !controllable {
+        return;
+    }
+    if let Ok(mut locked) = INPUT_LOCKED_UNTIL.lock()
+        && let Some(until) = *locked
+    {
+        if Instant::now() < until {
+            suppress_input();
+        } else {
+            *locked = None;
+        }
+    }
+    let Some(trap) = TRAP_QUEUE
+        .lock()
+        .ok()
+        .and_then(|mut queue| queue.pop_front())
+    else {
+        return;
+    };
+    log::info!("Setting off trap: {}", trap);
+    match trap {
+        Trap::HpDrain => drain_hp(&DMC1Memory),
+        Trap::MagicDrain => drain_magic(&DMC1Memory),
+        Trap::WeaponSwap => swap_weapons(),
+        Trap::RedOrbTax => tax_red_orbs(&DMC1Memory),
+        Trap::InputLock => {
+            if let Ok(mut locked) = INPUT_LOCKED_UNTIL.lock() {
+                *locked = Some(Instant::now() + INPUT_LOCK_DURATION);
+            }
+        }
+    }
+    overlay::add_message(OverlayMessage::new(
+        vec![
+            MessageSegment::new("Trap: ".to_string(), WHITE),
+            MessageSegment::new(trap.to_string(), RED),
+        ],
+        Duration::from_secs(3),
+        0.0,
+        0.0,
+        MessageType::Notification,
+    ));
+}
+
+pub(crate) fn drain_hp<M: GameMemory>(memory: &M) {
+    if let Err(e) = memory.with_player_data(|d| {
+        let drain = d.max_hp / HP_DRAIN_FRACTION;
+        d.hp = d.hp.saturating_sub(drain).max(1);
+    }) {
+        log::error!("Failed to drain hp: {:?}", e);
+    }
+}
+
+pub(crate) fn drain_magic<M: GameMemory>(memory: &M) {
+    if let Err(e) = memory.with_player_data(|d| {
+        d.magic_human = 0;
+        d.magic_demon = 0;
+    }) {
+        log::error!("Failed to drain magic: {:?}", e);
+    }
+}
+
+pub(crate) fn tax_red_orbs<M: GameMemory>(memory: &M) {
+    if let Err(e) = memory.with_session(|s| {
+        s.red_orbs -= s.red_orbs * RED_ORB_TAX_PERCENT / 100;
+    }) {
+        log::error!("Failed to tax red orbs: {:?}", e);
+    }
+}
+
+/// Switch to the next gun and melee weapon the player owns
+fn swap_weapons() {
+    let Ok(data) = ARCHIPELAGO_DATA.read() else {
+        return;
+    };
+    let owned = |map: &BiMap<&str, u8>| {
+        let mut owned: Vec<u8> = map
+            .iter()
+            .filter(|(name, _)| data.items.contains(**name))
+            .map(|(_, id)| *id)
+            .collect();
+        owned.sort();
+        owned
+    };
+    let next = |owned: &[u8], current: u8| {
+        owned
+            .iter()
+            .find(|id| **id > current)
+            .or(owned.first())
+            .copied()
+    };
+    let guns = owned(&GUN_MAP);
+    let melees = owned(&MELEE_MAP);
+    if let Err(e) = with_active_player_data(|d| {
+        if let Some(gun) = next(&guns, d.gun)
+            && gun != d.gun
+        {
+            d.gun = gun;
+            CHANGE_EQUIPPED_GUN(gun as u32);
+        }
+        if let Some(melee) = next(&melees, d.melee)
+            && melee != d.melee
+        {
+            d.melee = melee;
+            // Same as set_equipment, Sparda needs its own form
+            d.melee_form = if melee == 4 { 1 } else { 0 };
+            CHANGE_MELEE_FORM(d.melee_form as u32);
+            CHANGE_EQUIPPED_MELEE(melee as u32, 0);
+        }
+    }) {
+        log::error!("Failed to swap weapons: {:?}", e);
+    }
+}
+
+/// True while an input lock trap is going, the controller is blanked out for the game as well
+pub(crate) fn is_input_locked() -> bool {
+    INPUT_LOCKED_UNTIL
+        .lock()
+        .is_ok_and(|locked| locked.is_some_and(|until| Instant::now() < until))
+}
+
+/// Clear out the buttons and sticks the game has read for Dante. The controller itself is blanked out by the
+/// XInput hook, this covers the keyboard
+fn suppress_input() {
+    if let Err(e) = with_session(|s| {
+        s.buttons = [0; 4];
+        s.left_stick_x = 0x80;
+        s.left_stick_y = 0x80;
+        s.right_stick_x = 0x80;
+        s.right_stick_y = 0x80;
+    }) {
+        log::error!("Failed to suppress input: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_memory::MockMemory;
+
+    fn dante(difficulty: Difficulty, hp: u16, max_hp: u16) -> MockMemory {
+        let memory = MockMemory::new();
+        memory.session.borrow_mut().difficulty = difficulty as u8;
+        let mut player = memory.player.borrow_mut();
+        player.hp = hp;
+        player.max_hp = max_hp;
+        drop(player);
+        memory
+    }
+
+    fn percent_only(percent: f32, never_lethal: bool) -> HurtLink {
+        HurtLink {
+            easy_percent: percent,
+            normal_percent: percent,
+            hard_percent: percent,
+            dante_must_die_percent: percent,
+            flat_damage: 0,
+            never_lethal,
+        }
+    }
+
+    #[test]
+    fn damage_is_a_percentage_of_max_hp() {
+        let settings = HurtLink::default();
+        assert_eq!(hurt_link_damage(10000, Difficulty::Easy, &settings), 2500);
+        assert_eq!(
+            hurt_link_damage(10000, Difficulty::DanteMustDie, &settings),
+            8300
+        );
+    }
+
+    #[test]
+    fn flat_damage_is_added_on_top() {
+        let settings = HurtLink {
+            flat_damage: 100,
+            ..percent_only(10.0, false)
+        };
+        assert_eq!(hurt_link_damage(10000, Difficulty::Normal, &settings), 1100);
+        let flat_only = HurtLink {
+            flat_damage: 100,
+            ..percent_only(0.0, false)
+        };
+        assert_eq!(hurt_link_damage(10000, Difficulty::Normal, &flat_only), 100);
+    }
+
+    #[test]
+    fn damage_saturates_instead_of_overflowing() {
+        let settings = HurtLink {
+            flat_damage: u16::MAX,
+            ..percent_only(100.0, false)
+        };
+        assert_eq!(
+            hurt_link_damage(10000, Difficulty::Hard, &settings),
+            u16::MAX
+        );
+    }
+
+    #[test]
+    fn hp_stops_at_zero() {
+        let memory = dante(Difficulty::Hard, 3000, 10000);
+        hurt_dante(&memory, &percent_only(50.0, false));
+        assert_eq!(memory.player.borrow().hp, 0);
+    }
+
+    #[test]
+    fn never_lethal_leaves_one_hp() {
+        let memory = dante(Difficulty::Hard, 3000, 10000);
+        hurt_dante(&memory, &percent_only(50.0, true));
+        assert_eq!(memory.player.borrow().hp, 1);
+        // Still 1 after another hit
+        hurt_dante(&memory, &percent_only(50.0, true));
+        assert_eq!(memory.player.borrow().hp, 1);
+    }
+
+    #[test]
+    fn never_lethal_does_not_revive() {
+        let memory = dante(Difficulty::Hard, 0, 10000);
+        hurt_dante(&memory, &percent_only(50.0, true));
+        assert_eq!(memory.player.borrow().hp, 0);
+    }
+
+    #[test]
+    fn difficulty_picks_the_percentage() {
+        let settings = HurtLink::default();
+        let memory = dante(Difficulty::Easy, 10000, 10000);
+        hurt_dante(&memory, &settings);
+        assert_eq!(memory.player.borrow().hp, 7500);
+        let memory = dante(Difficulty::Normal, 10000, 10000);
+        hurt_dante(&memory, &settings);
+        assert_eq!(memory.player.borrow().hp, 6700);
+    }
+
+    /// Runs a frame with Dante at the given HP
+    fn frame(watch: &mut DeathWatch, memory: &MockMemory, hp: u16, now: Instant) -> Option<Death> {
+        memory.player.borrow_mut().hp = hp;
+        watch.update(memory, now)
+    }
+
+    #[test]
+    fn death_is_reported_once() {
+        let memory = dante(Difficulty::Normal, 10000, 10000);
+        let mut watch = DeathWatch::new();
+        let now = Instant::now();
+        assert_eq!(frame(&mut watch, &memory, 10000, now), None);
+        assert_eq!(frame(&mut watch, &memory, 0, now), Some(Death::Own));
+        // Lying dead on the game over screen
+        assert_eq!(frame(&mut watch, &memory, 0, now), None);
+    }
+
+    #[test]
+    fn link_kill_is_not_sent_back() {
+        let memory = dante(Difficulty::Normal, 10000, 10000);
+        let mut watch = DeathWatch::new();
+        let now = Instant::now();
+        frame(&mut watch, &memory, 10000, now);
+        watch.mark_link_kill(now);
+        assert_eq!(frame(&mut watch, &memory, 0, now), Some(Death::FromLink));
+        // The next death is Dante's own
+        frame(&mut watch, &memory, 10000, now);
+        assert_eq!(frame(&mut watch, &memory, 0, now), Some(Death::Own));
+    }
+
+    #[test]
+    fn link_kill_times_out() {
+        let memory = dante(Difficulty::Normal, 10000, 10000);
+        let mut watch = DeathWatch::new();
+        let now = Instant::now();
+        frame(&mut watch, &memory, 10000, now);
+        watch.mark_link_kill(now);
+        let later = now + LINK_KILL_TIMEOUT + Duration::from_secs(1);
+        frame(&mut watch, &memory, 10000, later);
+        assert_eq!(frame(&mut watch, &memory, 0, later), Some(Death::Own));
+    }
+
+    #[test]
+    fn link_kill_is_cleared_on_the_main_menu() {
+        let memory = dante(Difficulty::Normal, 10000, 10000);
+        let mut watch = DeathWatch::new();
+        let now = Instant::now();
+        frame(&mut watch, &memory, 10000, now);
+        watch.mark_link_kill(now);
+        memory.main_menu.set(true);
+        assert_eq!(frame(&mut watch, &memory, 10000, now), None);
+        memory.main_menu.set(false);
+        frame(&mut watch, &memory, 10000, now);
+        frame(&mut watch, &memory, 10000, now);
+        assert_eq!(frame(&mut watch, &memory, 0, now), Some(Death::Own));
+    }
+
+    #[test]
+    fn room_changes_are_not_deaths() {
+        let mut memory = dante(Difficulty::Normal, 10000, 10000);
+        let mut watch = DeathWatch::new();
+        let now = Instant::now();
+        frame(&mut watch, &memory, 10000, now);
+        frame(&mut watch, &memory, 10000, now);
+        // Actor torn down while the next room loads
+        memory.room += 1;
+        memory.player.borrow_mut().max_hp = 0;
+        assert_eq!(frame(&mut watch, &memory, 0, now), None);
+        assert_eq!(frame(&mut watch, &memory, 0, now), None);
+        memory.player.borrow_mut().max_hp = 10000;
+        assert_eq!(frame(&mut watch, &memory, 10000, now), None);
+        assert_eq!(frame(&mut watch, &memory, 0, now), Some(Death::Own));
+    }
+
+    /// Dante standing in room 0 with the room settled, ready to be trapped
+    fn settled_gate(memory: &MockMemory, start: Instant) -> (TrapGate, Instant) {
+        let mut gate = TrapGate::new();
+        assert!(!gate.update(memory, start));
+        let now = start + ROOM_SETTLE;
+        tick(memory);
+        assert!(gate.update(memory, now));
+        (gate, now)
+    }
+
+    /// The idle timer counting up, like it does every frame the game runs
+    fn tick(memory: &MockMemory) {
+        let mut player = memory.player.borrow_mut();
+        player.idle_timer = player.idle_timer.wrapping_add(1);
+    }
+
+    fn take_trap(
+        gate: &mut TrapGate,
+        memory: &MockMemory,
+        queue: &mut VecDeque<Trap>,
+        now: Instant,
+    ) -> Option<Trap> {
+        tick(memory);
+        if gate.update(memory, now) {
+            queue.pop_front()
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn traps_go_off_when_controllable() {
+        let memory = dante(Difficulty::Normal, 100, 100);
+        let (mut gate, now) = settled_gate(&memory, Instant::now());
+        let mut queue = VecDeque::from([Trap::HpDrain]);
+        assert_eq!(
+            take_trap(&mut gate, &memory, &mut queue, now),
+            Some(Trap::HpDrain)
+        );
+    }
+
+    #[test]
+    fn traps_wait_on_menus() {
+        let memory = dante(Difficulty::Normal, 100, 100);
+        let (mut gate, now) = settled_gate(&memory, Instant::now());
+        let mut queue = VecDeque::from([Trap::HpDrain]);
+        memory.main_menu.set(true);
+        assert_eq!(take_trap(&mut gate, &memory, &mut queue, now), None);
+        memory.main_menu.set(false);
+        memory.shop.set(true);
+        assert_eq!(take_trap(&mut gate, &memory, &mut queue, now), None);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn traps_wait_on_the_game_over_screen() {
+        let memory = dante(Difficulty::Normal, 100, 100);
+        let (mut gate, now) = settled_gate(&memory, Instant::now());
+        let mut queue = VecDeque::from([Trap::HpDrain]);
+        memory.player.borrow_mut().hp = 0;
+        assert_eq!(take_trap(&mut gate, &memory, &mut queue, now), None);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn traps_wait_for_the_room_to_load() {
+        let mut memory = dante(Difficulty::Normal, 100, 100);
+        let (mut gate, now) = settled_gate(&memory, Instant::now());
+        let mut queue = VecDeque::from([Trap::HpDrain]);
+        // Actor being rebuilt
+        memory.player.borrow_mut().max_hp = 0;
+        assert_eq!(take_trap(&mut gate, &memory, &mut queue, now), None);
+        memory.player.borrow_mut().max_hp = 100;
+        memory.room = 1;
+        assert_eq!(take_trap(&mut gate, &memory, &mut queue, now), None);
+        assert_eq!(
+            take_trap(&mut gate, &memory, &mut queue, now + ROOM_SETTLE / 2),
+            None
+        );
+        assert_eq!(queue.len(), 1);
+        assert_eq!(
+            take_trap(&mut gate, &memory, &mut queue, now + ROOM_SETTLE),
+            Some(Trap::HpDrain)
+        );
+    }
+
+    #[test]
+    fn traps_wait_while_paused() {
+        let memory = dante(Difficulty::Normal, 100, 100);
+        let (mut gate, now) = settled_gate(&memory, Instant::now());
+        let mut queue = VecDeque::from([Trap::HpDrain]);
+        for _ in 0..FROZEN_FRAMES {
+            gate.update(&memory, now);
+        }
+        assert!(!gate.update(&memory, now));
+        assert_eq!(queue.len(), 1);
+        // Unpausing gets the actor going again
+        assert_eq!(
+            take_trap(&mut gate, &memory, &mut queue, now),
+            Some(Trap::HpDrain)
+        );
+    }
 }
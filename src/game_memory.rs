@@ -0,0 +1,183 @@
+use crate::game_manager;
+use crate::game_manager::{PlayerData, PlayerDataError, SessionData, SessionError};
+use crate::utilities;
+use crate::utilities::DMC1_ADDRESS;
+use randomizer_utilities::read_data_from_address;
+#[cfg(test)]
+use std::cell::{Cell, RefCell};
+use std::ptr::write;
+
+/// Access to the parts of the game's memory the randomizer logic cares about.
+///
+/// Everything that reads or writes game state should go through this so the logic can be driven
+/// by something other than a running dmc1.exe.
+pub(crate) trait GameMemory {
+    fn with_session<F, R>(&self, f: F) -> Result<R, SessionError>
+    where
+        F: FnOnce(&mut SessionData) -> R;
+
+    fn with_player_data<F, R>(&self, f: F) -> Result<R, PlayerDataError>
+    where
+        F: FnOnce(&mut PlayerData) -> R;
+
+    /// Current room, -1 if unavailable
+    fn room(&self) -> i32;
+
+    /// Current track, -1 if unavailable
+    fn track(&self) -> i32;
+
+    /// Point the game at the room/track it should go to once the current one is done
+    fn set_next_room(&self, room: u32, track: u32) -> Result<(), PlayerDataError>;
+
+    /// Max HP/magic (in orbs) the game uses when it rebuilds Dante's actor, separate from the session
+    fn cached_stats(&self) -> (u8, u8);
+
+    fn set_cached_stats(&self, hp: u8, magic: u8);
+
+    fn on_main_menu(&self) -> bool;
+
+    fn in_shop(&self) -> bool;
+
+    /// Get current mission, 0 if the session isn't usable
+    fn mission(&self) -> u8 {
+        self.with_session(|s| s.mission).unwrap_or_default()
+    }
+}
+
+/// The actual game
+pub(crate) struct DMC1Memory;
+
+const CACHED_STATS: usize = 0x60b0d8;
+const CACHED_HP_OFFSET: usize = 0x98;
+const CACHED_MAGIC_OFFSET: usize = 0xA3;
+
+impl GameMemory for DMC1Memory {
+    fn with_session<F, R>(&self, f: F) -> Result<R, SessionError>
+    where
+        F: FnOnce(&mut SessionData) -> R,
+    {
+        game_manager::with_session(f)
+    }
+
+    fn with_player_data<F, R>(&self, f: F) -> Result<R, PlayerDataError>
+    where
+        F: FnOnce(&mut PlayerData) -> R,
+    {
+        game_manager::with_active_player_data(f)
+    }
+
+    fn room(&self) -> i32 {
+        game_manager::with_event_data_read(|s| s.room as i32).unwrap_or(-1)
+    }
+
+    fn track(&self) -> i32 {
+        game_manager::with_event_data_read(|s| s.track as i32).unwrap_or(-1)
+    }
+
+    fn set_next_room(&self, room: u32, track: u32) -> Result<(), PlayerDataError> {
+        game_manager::with_event_data(|e| {
+            e.next_room = room;
+            e.next_track = track;
+        })
+    }
+
+    fn cached_stats(&self) -> (u8, u8) {
+        let base = read_data_from_address::<usize>(*DMC1_ADDRESS + CACHED_STATS);
+        (
+            read_data_from_address::<u8>(base + CACHED_HP_OFFSET),
+            read_data_from_address::<u8>(base + CACHED_MAGIC_OFFSET),
+        )
+    }
+
+    fn set_cached_stats(&self, hp: u8, magic: u8) {
+        let base = read_data_from_address::<usize>(*DMC1_ADDRESS + CACHED_STATS);
+        unsafe {
+            write((base + CACHED_HP_OFFSET) as *mut u8, hp);
+            write((base + CACHED_MAGIC_OFFSET) as *mut u8, magic);
+        }
+    }
+
+    fn on_main_menu(&self) -> bool {
+        utilities::is_on_main_menu()
+    }
+
+    fn in_shop(&self) -> bool {
+        utilities::is_in_shop()
+    }
+}
+
+/// In-memory stand-in for the game, lets the randomizer flow run without dmc1.exe loaded
+#[cfg(test)]
+pub(crate) struct MockMemory {
+    pub(crate) session: RefCell<Box<SessionData>>,
+    pub(crate) player: RefCell<Box<PlayerData>>,
+    pub(crate) room: i32,
+    pub(crate) track: i32,
+    /// (room, track)
+    pub(crate) next_room: Cell<(u32, u32)>,
+    pub(crate) stats: Cell<(u8, u8)>,
+    pub(crate) main_menu: Cell<bool>,
+    pub(crate) shop: Cell<bool>,
+}
+
+#[cfg(test)]
+impl MockMemory {
+    pub(crate) fn new() -> Self {
+        Self {
+            session: RefCell::new(SessionData::zeroed()),
+            player: RefCell::new(PlayerData::zeroed()),
+            room: 0,
+            track: 1,
+            next_room: Cell::new((0, 0)),
+            stats: Cell::new((0, 0)),
+            main_menu: Cell::new(false),
+            shop: Cell::new(false),
+        }
+    }
+}
+
+#[cfg(test)]
+impl GameMemory for MockMemory {
+    fn with_session<F, R>(&self, f: F) -> Result<R, SessionError>
+    where
+        F: FnOnce(&mut SessionData) -> R,
+    {
+        Ok(f(&mut self.session.borrow_mut()))
+    }
+
+    fn with_player_data<F, R>(&self, f: F) -> Result<R, PlayerDataError>
+    where
+        F: FnOnce(&mut PlayerData) -> R,
+    {
+        Ok(f(&mut self.player.borrow_mut()))
+    }
+
+    fn room(&self) -> i32 {
+        self.room
+    }
+
+    fn track(&self) -> i32 {
+        self.track
+    }
+
+    fn set_next_room(&self, room: u32, track: u32) -> Result<(), PlayerDataError> {
+        self.next_room.set((room, track));
+        Ok(())
+    }
+
+    fn cached_stats(&self) -> (u8, u8) {
+        self.stats.get()
+    }
+
+    fn set_cached_stats(&self, hp: u8, magic: u8) {
+        self.stats.set((hp, magic));
+    }
+
+    fn on_main_menu(&self) -> bool {
+        self.main_menu.get()
+    }
+
+    fn in_shop(&self) -> bool {
+        self.shop.get()
+    }
+}
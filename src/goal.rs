@@ -0,0 +1,183 @@
+use crate::mapping::{Goal, Mapping};
+
+const FINAL_MISSION: u8 = 20;
+const MISSION_COUNT: u8 = 23;
+const SECRET_MISSION_COUNT: u8 = 12;
+
+/// Part of the goal, met once at least `count` of `locations` have been checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalRequirement {
+    pub locations: Vec<String>,
+    pub count: usize,
+}
+
+impl GoalRequirement {
+    fn single(location: String) -> Self {
+        GoalRequirement {
+            locations: vec![location],
+            count: 1,
+        }
+    }
+
+    pub fn is_met<S: AsRef<str>>(&self, checked: &[S]) -> bool {
+        self.locations
+            .iter()
+            .filter(|loc| checked.iter().any(|chk| chk.as_ref() == loc.as_str()))
+            .count()
+            >= self.count
+    }
+}
+
+fn mission_complete(mission: u8) -> String {
+    format!("Mission #{} Complete", mission)
+}
+
+/// Name of the location checked for clearing a secret mission. Clearing one rewards an orb, #12 is the only one with
+/// another location (the Bangle of Time) that can be picked up along the way.
+fn secret_mission_complete(number: u8) -> String {
+    match number {
+        SECRET_MISSION_COUNT => format!("Secret Mission #{} - Blue Orb", number),
+        _ => format!("Secret Mission #{}", number),
+    }
+}
+
+/// Every secret mission has to be cleared, picking something up inside one doesn't count
+fn secret_missions() -> Vec<GoalRequirement> {
+    (1..=SECRET_MISSION_COUNT)
+        .map(|number| GoalRequirement::single(secret_mission_complete(number)))
+        .collect()
+}
+
+/// Everything that needs to be done to reach the goal set in the slot data
+pub fn get_requirements(mapping: &Mapping) -> Vec<GoalRequirement> {
+    match mapping.goal {
+        Goal::Standard => vec![GoalRequirement::single(mission_complete(FINAL_MISSION))],
+        Goal::All => (1..=FINAL_MISSION)
+            .map(|mission| GoalRequirement::single(mission_complete(mission)))
+            .collect(),
+        Goal::RandomOrder => match &mapping.mission_order {
+            Some(order) if !order.is_empty() => {
+                vec![GoalRequirement::single(mission_complete(
+                    order[order.len() - 1],
+                ))]
+            }
+            _ => {
+                log::error!("RandomOrder goal without a mission order");
+                vec![GoalRequirement {
+                    locations: vec![],
+                    count: 1,
+                }]
+            }
+        },
+        Goal::SecretMissions => secret_missions(),
+        Goal::MissionCount => vec![GoalRequirement {
+            locations: (1..=MISSION_COUNT).map(mission_complete).collect(),
+            count: mapping.goal_mission_count as usize,
+        }],
+    }
+}
+
+/// Checks if the goal has been met, given the names of all checked locations
+pub fn has_reached_goal<S: AsRef<str>>(mapping: &Mapping, checked: &[S]) -> bool {
+    get_requirements(mapping)
+        .iter()
+        .all(|req| req.is_met(checked))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::generated_locations;
+    use serde_json::json;
+
+    fn mapping(goal: u8, mission_order: Option<Vec<u8>>, goal_mission_count: u8) -> Mapping {
+        serde_json::from_value(json!({
+            "starter_items": [],
+            "randomize_skills": false,
+            "purple_orb_mode": false,
+            "devil_trigger_mode": false,
+            "death_link": 0,
+            "goal": goal,
+            "mission_order": mission_order,
+            "goal_mission_count": goal_mission_count,
+        }))
+        .unwrap()
+    }
+
+    fn completed(missions: impl IntoIterator<Item = u8>) -> Vec<String> {
+        missions.into_iter().map(mission_complete).collect()
+    }
+
+    #[test]
+    fn requirements_are_real_locations() {
+        for goal in 0..=4 {
+            let mapping = mapping(goal, Some(vec![3, 1, 2]), 5);
+            for req in get_requirements(&mapping) {
+                for location in req.locations {
+                    assert!(
+                        generated_locations::ITEM_MISSION_MAP.contains_key(location.as_str()),
+                        "{} isn't a location",
+                        location
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn standard_needs_the_final_mission() {
+        let mapping = mapping(0, None, 0);
+        assert!(!has_reached_goal(&mapping, &completed(1..FINAL_MISSION)));
+        assert!(has_reached_goal(&mapping, &completed([FINAL_MISSION])));
+    }
+
+    #[test]
+    fn all_needs_every_mission() {
+        let mapping = mapping(1, None, 0);
+        assert!(!has_reached_goal(&mapping, &completed(2..=FINAL_MISSION)));
+        assert!(has_reached_goal(&mapping, &completed(1..=FINAL_MISSION)));
+    }
+
+    #[test]
+    fn random_order_needs_the_last_mission_in_the_order() {
+        let mapping = mapping(2, Some(vec![5, 20, 7]), 0);
+        assert!(!has_reached_goal(&mapping, &completed([5, 20])));
+        assert!(has_reached_goal(&mapping, &completed([7])));
+    }
+
+    #[test]
+    fn random_order_without_an_order_is_never_met() {
+        let mapping = mapping(2, None, 0);
+        assert!(!has_reached_goal(&mapping, &completed(1..=MISSION_COUNT)));
+    }
+
+    #[test]
+    fn secret_missions_need_all_twelve_cleared() {
+        let mapping = mapping(3, None, 0);
+        assert_eq!(get_requirements(&mapping).len(), 12);
+        let mut checked: Vec<String> = (1..=SECRET_MISSION_COUNT)
+            .map(secret_mission_complete)
+            .collect();
+        assert!(has_reached_goal(&mapping, &checked));
+        // Grabbing the Bangle of Time doesn't clear Secret Mission #12
+        checked.pop();
+        checked.push("Secret Mission #12 - Bangle of Time".to_string());
+        assert!(!has_reached_goal(&mapping, &checked));
+    }
+
+    #[test]
+    fn secret_missions_eleven_and_twelve_are_separate() {
+        let mapping = mapping(3, None, 0);
+        let checked: Vec<String> = (1..SECRET_MISSION_COUNT)
+            .map(secret_mission_complete)
+            .collect();
+        assert!(!has_reached_goal(&mapping, &checked));
+    }
+
+    #[test]
+    fn mission_count_needs_enough_missions() {
+        let mapping = mapping(4, None, 3);
+        assert!(!has_reached_goal(&mapping, &completed([1, 2])));
+        assert!(has_reached_goal(&mapping, &completed([4, 9, 22])));
+    }
+}
@@ -5,18 +5,22 @@ use crate::constants::{
     get_items_by_category,
 };
 use crate::game_manager::{
-    ARCHIPELAGO_DATA, CHANGE_EQUIPPED_GUN, CHANGE_EQUIPPED_MELEE, CHANGE_MELEE_FORM, get_room,
-    get_track, with_active_player_data, with_session, with_session_read,
+    ARCHIPELAGO_DATA, ArchipelagoData, CHANGE_EQUIPPED_GUN, CHANGE_EQUIPPED_MELEE,
+    CHANGE_MELEE_FORM, get_room, get_track, with_active_player_data, with_session,
+    with_session_read,
 };
+use crate::game_memory::{DMC1Memory, GameMemory};
 use crate::mapping::MAPPING;
 use crate::save_handler::setup_save_hooks;
+use crate::ui::overlay;
 use crate::ui::text_handler;
 use crate::ui::text_handler::ORIGINAL_DRAW_TEXT;
 use crate::utilities::DMC1_ADDRESS;
-use crate::{check_handler, constants, create_hook, save_handler, skill_manager, utilities};
+use crate::{
+    AP_CORE, archipelago, check_handler, constants, create_hook, data_storage, save_handler,
+    skill_manager, utilities,
+};
 use minhook::{MH_STATUS, MinHook};
-use randomizer_utilities::read_data_from_address;
-use std::ptr::write;
 use std::sync::atomic::Ordering;
 use std::sync::{LazyLock, OnceLock};
 
@@ -84,9 +88,11 @@ pub fn setup_new_session_data() {
     if let Some(func) = ORIGINAL_SETUP_NEW_SESSION.get() {
         unsafe { func() }
     }
+    give_starter_items();
     set_max_hp_and_magic();
     set_weapons_in_inv();
     set_equipment();
+    set_first_mission();
     with_active_player_data(|d| {
         with_session_read(|s| {
             d.hp = s.hp as u16 * 100;
@@ -98,6 +104,37 @@ pub fn setup_new_session_data() {
     .unwrap();
 }
 
+/// A new save needs the starter items' consumables and red orbs. Everything else is already in
+/// ARCHIPELAGO_DATA from when the items were synced, so a scratch copy keeps it from being counted twice.
+/// HP and magic from orbs get overwritten by set_max_hp_and_magic afterward.
+fn give_starter_items() {
+    match AP_CORE.get().map(|core| core.lock()) {
+        Some(Ok(core)) => {
+            if let Some(client) = core.client() {
+                archipelago::give_starter_items(
+                    &DMC1Memory,
+                    &mut ArchipelagoData::default(),
+                    client,
+                    true,
+                );
+            }
+        }
+        Some(Err(err)) => log::error!("Failed to lock core for starter items: {}", err),
+        None => {}
+    }
+}
+
+/// Start at the first mission of the order for the RandomOrder goal
+fn set_first_mission() {
+    if let Some(mapping) = MAPPING.read().unwrap().as_ref()
+        && let Some(first) = mapping.first_mission()
+    {
+        log::debug!("Starting at Mission #{}", first);
+        check_handler::start_mission_at(first);
+        overlay::set_next_mission(first);
+    }
+}
+
 const LOAD_ROOM_ADDR: usize = 0x255cc0;
 static ORIGINAL_LOAD_ROOM: OnceLock<BasicNothingFunc> = OnceLock::new();
 
@@ -113,7 +150,9 @@ fn load_room() {
         set_equipment();
     }
     set_relevant_key_items();
-    skill_manager::set_skills(&ARCHIPELAGO_DATA.read().unwrap());
+    skill_manager::set_skills(&DMC1Memory, &ARCHIPELAGO_DATA.read().unwrap());
+    data_storage::set_current_mission(DMC1Memory.mission());
+    check_handler::verify_redirect();
 }
 
 fn set_max_hp_and_magic() {
@@ -129,18 +168,10 @@ fn set_max_hp_and_magic() {
                 }
             })
             .unwrap();
-            let something = read_data_from_address::<usize>(*DMC1_ADDRESS + 0x60b0d8);
-            let something_hp = something + 0x98;
-            unsafe {
-                write(something_hp as *mut u8, INITIAL_HP + data.blue_orbs as u8);
-            }
-            let something_magic = something + 0xA3;
-            unsafe {
-                write(
-                    something_magic as *mut u8,
-                    INITIAL_MAGIC + data.purple_orbs as u8,
-                );
-            }
+            DMC1Memory.set_cached_stats(
+                INITIAL_HP + data.blue_orbs as u8,
+                INITIAL_MAGIC + data.purple_orbs as u8,
+            );
             with_active_player_data(|d| {
                 d.max_hp = u8::min(INITIAL_HP + data.blue_orbs as u8, MAX_HP) as u16 * 100;
                 if data.dt_unlocked {
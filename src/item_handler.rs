@@ -0,0 +1,255 @@
+use crate::constants::{ITEM_DATA_MAP, MISSION_ITEM_MAP};
+use crate::game_manager::{ArchipelagoData, SessionError};
+use crate::game_memory::GameMemory;
+use crate::{game_manager, skill_manager};
+
+/// Applies a single item to the game. `is_new` is false when re-applying items that were already given before
+/// (i.e. loading a save), so consumables and orbs aren't handed out twice
+pub(crate) fn give_item<M: GameMemory>(
+    memory: &M,
+    data: &mut ArchipelagoData,
+    item_id: i64,
+    item_name: &str,
+    is_new: bool,
+    randomize_skills: bool,
+) {
+    if let Some(trap) = game_manager::Trap::from_item_name(item_name) {
+        // Already sprung if the item isn't new
+        if is_new {
+            game_manager::queue_trap(trap);
+        }
+        return;
+    }
+    match item_id {
+        41..=43 => {
+            if is_new {
+                let orbs = match item_id {
+                    41 => 100,
+                    42 => 150,
+                    43 => 200,
+                    _ => unreachable!(),
+                };
+                game_manager::give_red_orbs(memory, orbs);
+            }
+        }
+        1..=5 | 8..=11 | 17 => {
+            // Guns, melee weapons and the Bangle of Time
+            if let Some(item_data) = ITEM_DATA_MAP.get(item_name) {
+                add_to_inventory(
+                    item_name,
+                    memory.with_session(|s| s.insert_unique_item(item_data)),
+                );
+            }
+        }
+        6 => {
+            data.add_blue_orb();
+            game_manager::give_hp(memory, 1);
+        }
+        7 => {
+            data.add_purple_orb();
+            game_manager::give_magic(memory, 1);
+        }
+        12..=16 => {
+            // Don't add duplicate consumables
+            if is_new {
+                if item_id == 15 {
+                    memory
+                        .with_session(|session| {
+                            session.yellow_orbs += 1;
+                        })
+                        .unwrap_or_else(|e| log::error!("Failed to give yellow orb: {:?}", e));
+                } else if let Some(item_data) = ITEM_DATA_MAP.get(item_name) {
+                    add_to_inventory(item_name, memory.with_session(|s| s.insert_item(item_data)));
+                }
+            }
+        }
+        39 => {
+            // DT Unlock
+            data.add_dt();
+            game_manager::give_magic(memory, 3);
+        }
+        18..=38 => {
+            // For key items
+            log::debug!("Setting newly acquired key items");
+            if let Some(item_list) = MISSION_ITEM_MAP.get(&memory.mission())
+                && item_list.contains(&item_name)
+                && let Some(item_data) = ITEM_DATA_MAP.get(item_name)
+            {
+                add_to_inventory(
+                    item_name,
+                    memory.with_session(|s| s.insert_unique_item(item_data)),
+                );
+            }
+        }
+        100..=113 => {
+            // For skills
+            if randomize_skills {
+                skill_manager::add_skill(item_id as usize, data);
+                skill_manager::set_skills(memory, data); // Hacky...
+            }
+        }
+        _ => {
+            log::warn!("Unhandled item ID: {} ({})", item_name, item_id)
+        }
+    }
+}
+
+/// Reports an item that didn't make it into the inventory
+fn add_to_inventory(item_name: &str, result: Result<bool, SessionError>) {
+    match result {
+        Ok(true) => {}
+        Ok(false) => log::error!("Inventory is full, unable to give {item_name}"),
+        Err(e) => log::error!("Failed to give {item_name}: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_memory::MockMemory;
+
+    fn inventory(memory: &MockMemory) -> Vec<(u8, u8, u16)> {
+        let session = memory.session.borrow();
+        session.item_data[..session.item_count as usize]
+            .iter()
+            .map(|item| (item.category, item.id, item.count))
+            .collect()
+    }
+
+    #[test]
+    fn red_orbs_are_only_given_once() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        give_item(&memory, &mut data, 42, "Red Orb - 150", true, false);
+        assert_eq!(memory.session.borrow().red_orbs, 150);
+        give_item(&memory, &mut data, 42, "Red Orb - 150", false, false);
+        assert_eq!(memory.session.borrow().red_orbs, 150);
+    }
+
+    #[test]
+    fn blue_orbs_are_reapplied() {
+        let memory = MockMemory::new();
+        memory.player.borrow_mut().hp = 1000;
+        memory.player.borrow_mut().max_hp = 1000;
+        memory.set_cached_stats(10, 0);
+        let mut data = ArchipelagoData::default();
+        give_item(&memory, &mut data, 6, "Blue Orb", true, false);
+        give_item(&memory, &mut data, 6, "Blue Orb", false, false);
+        assert_eq!(data.blue_orbs, 2);
+        assert_eq!(memory.session.borrow().hp, 2);
+        assert_eq!(memory.cached_stats(), (12, 0));
+        assert_eq!(memory.player.borrow().hp, 1200);
+        assert_eq!(memory.player.borrow().max_hp, 1200);
+    }
+
+    #[test]
+    fn weapons_are_not_duplicated() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        give_item(&memory, &mut data, 2, "Shotgun", true, false);
+        give_item(&memory, &mut data, 2, "Shotgun", false, false);
+        let shotgun = ITEM_DATA_MAP["Shotgun"];
+        assert_eq!(inventory(&memory), vec![(shotgun.category, shotgun.id, 1)]);
+    }
+
+    #[test]
+    fn consumables_stack_but_are_not_reapplied() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        give_item(&memory, &mut data, 12, "Vital Star", true, false);
+        give_item(&memory, &mut data, 12, "Vital Star", true, false);
+        give_item(&memory, &mut data, 12, "Vital Star", false, false);
+        let star = ITEM_DATA_MAP["Vital Star"];
+        assert_eq!(inventory(&memory), vec![(star.category, star.id, 2)]);
+    }
+
+    #[test]
+    fn yellow_orbs_go_to_the_counter() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        give_item(&memory, &mut data, 15, "Yellow Orb", true, false);
+        assert_eq!(memory.session.borrow().yellow_orbs, 1);
+        assert!(inventory(&memory).is_empty());
+    }
+
+    #[test]
+    fn key_items_are_only_given_in_their_mission() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        memory.session.borrow_mut().mission = 15;
+        give_item(&memory, &mut data, 30, "Staff of Hermes", true, false);
+        assert!(inventory(&memory).is_empty());
+
+        memory.session.borrow_mut().mission = 16;
+        give_item(&memory, &mut data, 30, "Staff of Hermes", false, false);
+        let staff = ITEM_DATA_MAP["Staff of Hermes"];
+        assert_eq!(inventory(&memory), vec![(staff.category, staff.id, 1)]);
+    }
+
+    #[test]
+    fn skills_are_ignored_unless_randomized() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        give_item(&memory, &mut data, 100, "Stinger", true, false);
+        assert!(data.skills.is_empty());
+        assert_eq!(data.stinger_level, 0);
+    }
+
+    #[test]
+    fn skill_levels_progress() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        give_item(&memory, &mut data, 100, "Stinger", true, true);
+        assert_eq!(data.stinger_level, 1);
+        assert_eq!(memory.session.borrow().expertise[3], 16);
+        give_item(&memory, &mut data, 100, "Stinger", true, true);
+        assert_eq!(data.stinger_level, 2);
+        assert!(data.skills.contains("Alastor - Stinger Level 2"));
+        assert_eq!(memory.session.borrow().expertise[3], 16 | 8);
+    }
+
+    #[test]
+    fn traps_go_by_name() {
+        use game_manager::Trap;
+        assert_eq!(Trap::from_item_name("HP Drain Trap"), Some(Trap::HpDrain));
+        assert_eq!(Trap::from_item_name("Red Orb Tax"), Some(Trap::RedOrbTax));
+        assert_eq!(
+            Trap::from_item_name("Input Lock Trap"),
+            Some(Trap::InputLock)
+        );
+        assert_eq!(Trap::from_item_name("Blue Orb"), None);
+        assert_eq!(Trap::from_item_name("Trap"), None);
+    }
+
+    #[test]
+    fn traps_are_not_given_as_items() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        // Whatever ID the trap has, it must not be treated as the item that ID used to be
+        give_item(&memory, &mut data, 6, "Magic Drain Trap", false, false);
+        assert_eq!(data.blue_orbs, 0);
+        assert_eq!(memory.session.borrow().red_orbs, 0);
+    }
+
+    #[test]
+    fn full_inventory_does_not_panic() {
+        let memory = MockMemory::new();
+        let mut data = ArchipelagoData::default();
+        let star = ITEM_DATA_MAP["Vital Star"];
+        {
+            let mut session = memory.session.borrow_mut();
+            let slots = session.item_data.len();
+            session.item_data[slots - 1] = star;
+            session.item_count = slots as u8;
+        }
+        give_item(&memory, &mut data, 2, "Shotgun", true, false);
+        give_item(&memory, &mut data, 12, "Vital Star", true, false);
+        let session = memory.session.borrow();
+        assert_eq!(session.item_count as usize, session.item_data.len());
+        // Items already in the inventory still stack
+        assert_eq!(
+            session.item_data[session.item_data.len() - 1].count,
+            star.count * 2
+        );
+    }
+}
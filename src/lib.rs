@@ -1,7 +1,6 @@
 use crate::archipelago::ArchipelagoCore;
 use crate::constants::{BasicNothingFunc, DMC1Config};
 use crate::utilities::{DMC1_ADDRESS, is_ddmk_loaded};
-use archipelago_rs::{Connection, ConnectionOptions, ItemHandling};
 use minhook::{MH_STATUS, MinHook};
 use randomizer_utilities::dmc::dmc_constants::GameConfig;
 use randomizer_utilities::exception_handler;
@@ -16,11 +15,21 @@ mod compat;
 mod config;
 mod constants;
 mod data;
+mod data_storage;
+#[cfg(test)]
+mod fake_server;
 mod game_manager;
+mod game_memory;
+mod goal;
 mod hook;
+mod item_handler;
 mod location_handler;
 mod mapping;
+mod mission_select;
+mod reconnect;
+mod save_file;
 mod save_handler;
+mod save_manager;
 mod skill_manager;
 mod ui;
 mod utilities;
@@ -101,31 +110,28 @@ fn main_loop_hook() {
         }
     }
 
-    if !config::CONFIG.connections.disable_auto_connect
-        && let Ok(mut core) = AP_CORE
-            .get_or_init(|| {
-                ArchipelagoCore::new(
-                    config::CONFIG.connections.get_url(),
-                    DMC1Config::GAME_NAME.parse().unwrap(),
-                )
-                .map(|core| Arc::new(Mutex::new(core)))
-                .unwrap()
-            })
-            .lock()
+    if let Ok(mut core) = AP_CORE
+        .get_or_init(|| {
+            ArchipelagoCore::new(
+                config::get_last_server_details(),
+                DMC1Config::GAME_NAME.parse().unwrap(),
+                !config::CONFIG.connections.disable_auto_connect,
+            )
+            .map(|core| Arc::new(Mutex::new(core)))
+            .unwrap()
+        })
+        .lock()
         && let Err(err) = core.update()
     {
         log::error!("{}", err);
-        log::debug!("Attempting to reconnect");
-        core.connection = Connection::new(
-            config::CONFIG.connections.get_url(),
-            DMC1Config::GAME_NAME,
-            "",
-            ConnectionOptions::new().receive_items(ItemHandling::OtherWorlds {
-                own_world: true,
-                starting_inventory: true,
-            }),
-        );
     }
+    game_manager::check_for_death();
+    game_manager::process_traps();
+    skill_manager::update_shop_expertise(
+        &game_memory::DMC1Memory,
+        utilities::is_in_shop(),
+        &game_manager::ARCHIPELAGO_DATA.read().unwrap(),
+    );
 }
 
 fn main_setup() {
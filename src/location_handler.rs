@@ -1,9 +1,9 @@
 use crate::check_handler::{Location, LocationType};
-use crate::constants::{ITEM_DATA_MAP, REMOTE_ID};
+use crate::constants::{Difficulty, ITEM_DATA_MAP, ItemEntry, REMOTE_ID};
 use crate::data::generated_locations;
 use crate::game_manager::ItemData;
 use crate::mapping::Mapping;
-use crate::{constants, mapping};
+use crate::{constants, mapping, skill_manager};
 use archipelago_rs::Client;
 use randomizer_utilities::archipelago_utilities;
 use std::error::Error;
@@ -12,6 +12,24 @@ pub fn get_location_name_by_data(
     location_data: &Location,
     client: &Client<Mapping>,
 ) -> Result<&'static str, Box<dyn Error>> {
+    resolve_location_name(
+        location_data,
+        |name| client.this_game().item_by_name(name).map(|item| item.id()),
+        |key| client.checked_locations().any(|loc| loc.name() == key),
+    )
+}
+
+/// Finds the location key for the given data, `ap_item_id` is used to get the AP item ID for an in game item name
+/// and `is_checked` tells if a location has already been checked
+pub fn resolve_location_name<F, C>(
+    location_data: &Location,
+    ap_item_id: F,
+    is_checked: C,
+) -> Result<&'static str, Box<dyn Error>>
+where
+    F: Fn(&str) -> Option<i64>,
+    C: Fn(&str) -> bool,
+{
     if location_data.location_type != LocationType::Standard
         && let Some(location) =
             generated_locations::ITEM_MISSION_MAP
@@ -21,8 +39,19 @@ pub fn get_location_name_by_data(
                     LocationType::MissionComplete => {
                         *(*key) == format!("Mission #{} Complete", location_data.mission).as_str()
                     }
-                    LocationType::SSRank => {
-                        *(*key) == format!("Mission #{} SS Rank", location_data.mission).as_str()
+                    LocationType::MissionCompleteDifficulty => {
+                        *(*key)
+                            == format!(
+                                "Mission #{} Complete ({})",
+                                location_data.mission,
+                                Difficulty::from_repr(location_data.item_id as usize)
+                                    .map(|difficulty| difficulty.to_string())
+                                    .unwrap_or_default()
+                            )
+                            .as_str()
+                    }
+                    LocationType::MissionRank => {
+                        *(*key) == format!("Mission #{} S Rank", location_data.mission).as_str()
                     }
                     LocationType::PurchaseItem => {
                         *(*key)
@@ -36,6 +65,12 @@ pub fn get_location_name_by_data(
                                             _ => unreachable!(),
                                         }
                                     }
+                                    constants::ALASTOR_STORE | constants::IFRIT_STORE => {
+                                        skill_manager::ID_SKILL_MAP
+                                            .get(&(location_data.item_id as usize))
+                                            .map(|skill| skill.to_string())
+                                            .unwrap_or_default()
+                                    }
                                     _ => unreachable!(),
                                 }
                             )
@@ -45,36 +80,77 @@ pub fn get_location_name_by_data(
         return Ok(location.0);
     }
 
-    let filtered_locs =
+    // Wew.
+    let picked_up_id = if let Some(item_data) = constants::find_item_by_data(&ItemData {
+        category: location_data.item_category,
+        id: location_data.item_id as u8,
+        count: 1,
+    }) {
+        ap_item_id(item_data).unwrap_or(-1)
+    } else {
+        log::debug!("Item isn't in constants, see pickup message");
+        -1
+    };
+    resolve_pickup(
         generated_locations::ITEM_MISSION_MAP
             .iter()
-            .filter(|(_key, item_entry)| {
-                (item_entry.room_number == location_data.room)
-                    && (item_entry.track_number == location_data.track)
-                    && ((!item_entry.coordinates.has_coords())
-                        || item_entry.coordinates == location_data.coordinates)
-            });
-    for (key, entry) in filtered_locs {
-        // Wew.
-        if entry.item_id as i64
-            == (if let Some(item_data) = constants::find_item_by_data(&ItemData {
-                category: location_data.item_category,
-                id: location_data.item_id as u8,
-                count: 1,
-            }) {
-                if let Some(item) = client.this_game().item_by_name(item_data) {
-                    item.id()
-                } else {
-                    -1
-                }
-            } else {
-                log::debug!("Item isn't in constants, see pickup message");
-                -1
+            .map(|(key, item_entry)| (*key, item_entry)),
+        location_data,
+        picked_up_id,
+        is_checked,
+    )
+}
+
+/// Picks the location for an item picked up off the ground out of `entries`
+fn resolve_pickup<'a, C>(
+    entries: impl Iterator<Item = (&'static str, &'a ItemEntry)>,
+    location_data: &Location,
+    picked_up_id: i64,
+    is_checked: C,
+) -> Result<&'static str, Box<dyn Error>>
+where
+    C: Fn(&str) -> bool,
+{
+    let candidates: Vec<(&'static str, &ItemEntry)> = entries
+        .filter(|(_key, item_entry)| {
+            (item_entry.room_number == location_data.room)
+                && (item_entry.track_number == location_data.track)
+                && (item_entry.item_id as i64 == picked_up_id
+                    || location_data.item_id == *REMOTE_ID)
+        })
+        .collect();
+
+    // Pickups with coordinates set can be told apart from others of the same kind in the room
+    if location_data.coordinates.has_coords()
+        && let Some(&(key, _entry)) = candidates
+            .iter()
+            .filter(|(_key, entry)| {
+                entry.coordinates.has_coords()
+                    && entry.coordinates.is_near(&location_data.coordinates)
             })
-            || location_data.item_id == *REMOTE_ID
-        {
-            return Ok(key);
-        }
+            .min_by_key(|(_key, entry)| {
+                entry
+                    .coordinates
+                    .distance_squared(&location_data.coordinates)
+            })
+    {
+        return Ok(key);
+    }
+
+    // Otherwise it's the only one of its kind in the room for its mission (build.rs makes sure
+    // duplicates have coordinates), preferring the current mission and one that isn't checked yet
+    if let Some(&(key, _entry)) = candidates
+        .iter()
+        .filter(|(_key, entry)| !entry.coordinates.has_coords())
+        .min_by_key(|(key, entry)| {
+            (
+                entry.mission != location_data.mission,
+                is_checked(key),
+                *key,
+            )
+        })
+    {
+        return Ok(key);
     }
     Err(Box::from("No location found"))
 }
@@ -103,29 +179,159 @@ pub fn get_mapped_data(location_name: &str) -> Result<ItemData, Box<dyn Error>>
             *REMOTE_ID
         }
     };
+    let item_name = opt_item.map(|item| item.item().name().to_string());
+    Ok(map_item_to_data(id, item_name.as_deref()))
+}
+
+/// Get the in game item to display for an AP item ID, `item_name` is only present for items belonging to this world
+pub fn map_item_to_data(id: u32, item_name: Option<&str>) -> ItemData {
     // Red Orbs
     if 43 >= id && id > 40 {
-        return Ok(*ITEM_DATA_MAP.get("Red Orb - 1").unwrap());
+        return *ITEM_DATA_MAP.get("Red Orb - 1").unwrap();
     }
 
     // To set the displayed graphic to the corresponding weapon
     if id >= 100 {
-        return Ok(match id {
+        return match id {
             (100..=105) => *ITEM_DATA_MAP.get("Alastor").unwrap(),
             (107..=113) => *ITEM_DATA_MAP.get("Ifrit").unwrap(),
             _ => {
                 log::error!("Unrecognized id {}, default to Remote", id);
                 get_remote_data()
             }
-        });
+        };
     }
-    if let Some(opt_item) = opt_item {
-        Ok(*ITEM_DATA_MAP.get(opt_item.item().name().as_str()).unwrap())
-    } else {
-        Ok(get_remote_data())
+    match item_name.and_then(|name| ITEM_DATA_MAP.get(name)) {
+        Some(data) => *data,
+        None => get_remote_data(),
     }
 }
 
 pub fn get_remote_data() -> ItemData {
     *ITEM_DATA_MAP.get("Remote").unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{Coordinates, EMPTY_COORDINATES};
+
+    fn location(location_type: LocationType, mission: u32) -> Location {
+        Location {
+            location_type,
+            item_id: 0,
+            room: 0,
+            track: 0,
+            mission,
+            coordinates: EMPTY_COORDINATES,
+            item_category: 0,
+        }
+    }
+
+    fn no_items(_name: &str) -> Option<i64> {
+        None
+    }
+
+    fn unchecked(_key: &str) -> bool {
+        false
+    }
+
+    #[test]
+    fn resolves_mission_completion() {
+        let complete = location(LocationType::MissionComplete, 7);
+        assert_eq!(
+            resolve_location_name(&complete, no_items, unchecked).unwrap(),
+            "Mission #7 Complete"
+        );
+        let rank = location(LocationType::MissionRank, 3);
+        assert_eq!(
+            resolve_location_name(&rank, no_items, unchecked).unwrap(),
+            "Mission #3 S Rank"
+        );
+    }
+
+    #[test]
+    fn resolves_shop_purchases() {
+        let mut blue_orb = location(LocationType::PurchaseItem, 2);
+        blue_orb.item_category = constants::EXTRA_STORE;
+        blue_orb.item_id = 5;
+        assert_eq!(
+            resolve_location_name(&blue_orb, no_items, unchecked).unwrap(),
+            "Purchase Blue Orb #2"
+        );
+        let mut stinger = location(LocationType::PurchaseItem, 0);
+        stinger.item_category = constants::ALASTOR_STORE;
+        stinger.item_id = 100;
+        assert_eq!(
+            resolve_location_name(&stinger, no_items, unchecked).unwrap(),
+            "Purchase Alastor - Stinger Level 1"
+        );
+    }
+
+    #[test]
+    fn resolves_pickup_by_room_and_item() {
+        let key = ITEM_DATA_MAP["Rusty Key (Mission #1)"];
+        let pickup = Location {
+            location_type: LocationType::Standard,
+            item_id: key.id as u32,
+            room: 3,
+            track: 1,
+            mission: 1,
+            coordinates: EMPTY_COORDINATES,
+            item_category: key.category,
+        };
+        let ap_item_id = |name: &str| (name == "Rusty Key (Mission #1)").then_some(36);
+        assert_eq!(
+            resolve_location_name(&pickup, ap_item_id, unchecked).unwrap(),
+            "Mission #1 - Rusty Key"
+        );
+        // Same item somewhere it was never placed
+        let elsewhere = Location { room: 4, ..pickup };
+        assert!(resolve_location_name(&elsewhere, ap_item_id, unchecked).is_err());
+    }
+
+    #[test]
+    fn duplicate_pickups_go_by_coordinates() {
+        let fragment = |x: i32, z: i32| ItemEntry {
+            room_number: 12,
+            track_number: 1,
+            item_id: 35,
+            mission: 3,
+            coordinates: Coordinates { x, y: 0, z },
+        };
+        let first = fragment(1500, -300);
+        let second = fragment(-2200, 800);
+        let entries = || {
+            [
+                ("Mission #3 - Blue Orb Fragment #7", &first),
+                ("Mission #3 - Blue Orb Fragment #8", &second),
+            ]
+            .into_iter()
+        };
+        let pickup_at = |x: i32, z: i32| Location {
+            location_type: LocationType::Standard,
+            item_id: 0,
+            room: 12,
+            track: 1,
+            mission: 3,
+            coordinates: Coordinates { x, y: 5, z },
+            item_category: 0,
+        };
+        // Picked up in either order, each goes to the fragment that was actually there
+        assert_eq!(
+            resolve_pickup(entries(), &pickup_at(-2210, 790), 35, unchecked).unwrap(),
+            "Mission #3 - Blue Orb Fragment #8"
+        );
+        assert_eq!(
+            resolve_pickup(entries(), &pickup_at(1490, -310), 35, unchecked).unwrap(),
+            "Mission #3 - Blue Orb Fragment #7"
+        );
+        // Checked already doesn't change which pickup it was
+        assert_eq!(
+            resolve_pickup(entries(), &pickup_at(1500, -300), 35, |_| true).unwrap(),
+            "Mission #3 - Blue Orb Fragment #7"
+        );
+        // Nowhere near either of them
+        assert!(resolve_pickup(entries(), &pickup_at(0, 5000), 35, unchecked).is_err());
+    }
+}
@@ -1,9 +1,11 @@
+use crate::constants::Rank;
 use crate::data::generated_locations;
 use archipelago_rs::{Client, CreateAsHint, Location};
 use randomizer_utilities::{APVersion, archipelago_utilities};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use std::sync::{LazyLock, RwLock};
+use std::time::Instant;
 pub static OVERLAY_INFO: LazyLock<RwLock<OverlayInfo>> =
     LazyLock::new(|| RwLock::new(OverlayInfo::default()));
 
@@ -11,6 +13,10 @@ pub static OVERLAY_INFO: LazyLock<RwLock<OverlayInfo>> =
 pub struct OverlayInfo {
     pub client_version: Option<APVersion>,
     pub generated_version: Option<APVersion>,
+    /// Upcoming mission when the mission order is being enforced
+    pub next_mission: Option<u8>,
+    /// When the next reconnection attempt happens, if the connection is down
+    pub reconnect_at: Option<Instant>,
 }
 
 pub static MAPPING: LazyLock<RwLock<Option<Mapping>>> = LazyLock::new(|| RwLock::new(None));
@@ -27,6 +33,18 @@ fn default_goal() -> Goal {
     Goal::Standard
 }
 
+fn default_goal_mission_count() -> u8 {
+    20
+}
+
+fn default_rank() -> Rank {
+    Rank::S
+}
+
+fn default_hint_scouts() -> HintScouts {
+    HintScouts::Shop
+}
+
 /// Converts the option number from the slot data into a more usable gun name
 fn parse_gun_number<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
@@ -79,6 +97,28 @@ where
     }
 }
 
+fn parse_hint_scouts<'de, D>(deserializer: D) -> Result<HintScouts, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let val = Value::deserialize(deserializer)?;
+    match val {
+        Value::Number(n) => match n.as_i64().unwrap_or_default() {
+            0 => Ok(HintScouts::Off),
+            1 => Ok(HintScouts::Shop),
+            2 => Ok(HintScouts::All),
+            _ => Err(serde::de::Error::custom(format!(
+                "Invalid hint scout option: {}",
+                n
+            ))),
+        },
+        other => Err(serde::de::Error::custom(format!(
+            "Unexpected type: {:?}",
+            other
+        ))),
+    }
+}
+
 /// Figure out which DL setting were on
 fn parse_death_link<'de, D>(deserializer: D) -> Result<DeathlinkSetting, D::Error>
 where
@@ -113,6 +153,8 @@ where
             0 => Ok(Goal::Standard),
             1 => Ok(Goal::All),
             2 => Ok(Goal::RandomOrder),
+            3 => Ok(Goal::SecretMissions),
+            4 => Ok(Goal::MissionCount),
             _ => Err(serde::de::Error::custom(format!(
                 "Invalid goal option: {}",
                 n
@@ -125,6 +167,23 @@ where
     }
 }
 
+/// Parse the lowest rank that still counts for mission rank checks
+fn parse_rank<'de, D>(deserializer: D) -> Result<Rank, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let val = Value::deserialize(deserializer)?;
+    match val {
+        Value::Number(n) => Rank::from_repr(n.as_i64().unwrap_or_default() as usize).ok_or(
+            serde::de::Error::custom(format!("Invalid rank option: {}", n)),
+        ),
+        other => Err(serde::de::Error::custom(format!(
+            "Unexpected type: {:?}",
+            other
+        ))),
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Mapping {
     // For mapping JSON
@@ -144,10 +203,49 @@ pub struct Mapping {
     #[serde(deserialize_with = "parse_goal")]
     pub goal: Goal,
     pub mission_order: Option<Vec<u8>>,
+    /// How many missions need to be beaten for the MissionCount goal
+    #[serde(default = "default_goal_mission_count")]
+    pub goal_mission_count: u8,
+    /// Adds "Mission #N Complete (Hard)" and "Mission #N Complete (Dante Must Die)" checks
+    #[serde(default)]
+    pub difficulty_checks: bool,
+    /// Adds a "Mission #N S Rank" check for each mission
+    #[serde(default)]
+    pub rank_checks: bool,
+    /// Lowest rank that still sends the mission's rank check
+    #[serde(default = "default_rank")]
+    #[serde(deserialize_with = "parse_rank")]
+    pub minimum_rank: Rank,
+    /// Which scouted locations get announced as hints, the config can override this
+    #[serde(default = "default_hint_scouts")]
+    #[serde(deserialize_with = "parse_hint_scouts")]
+    pub hint_scouts: HintScouts,
     pub generated_version: Option<APVersion>,
     pub client_version: Option<APVersion>,
 }
 
+impl Mapping {
+    /// Order the missions need to be played in, only present for the RandomOrder goal
+    fn enforced_order(&self) -> Option<&Vec<u8>> {
+        if self.goal == Goal::RandomOrder {
+            self.mission_order.as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn first_mission(&self) -> Option<u8> {
+        self.enforced_order()?.first().copied()
+    }
+
+    /// The mission that should be played after `completed`, None if the vanilla order should be kept
+    pub fn next_mission(&self, completed: u8) -> Option<u8> {
+        let order = self.enforced_order()?;
+        let idx = order.iter().position(|mission| *mission == completed)?;
+        order.get(idx + 1).copied()
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub enum Goal {
     /// Beat M20 in linear order M1-M20 (Default)
@@ -156,6 +254,20 @@ pub enum Goal {
     All,
     /// Beat all missions in a randomized linear order
     RandomOrder,
+    /// Complete every secret mission
+    SecretMissions,
+    /// Beat a number of missions (goal_mission_count) of the player's choice
+    MissionCount,
+}
+
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub enum HintScouts {
+    /// Scouting never creates hints
+    Off,
+    /// Only the shop's contents are hinted, since they can be seen before buying (Default)
+    Shop,
+    /// Every location is hinted as soon as it's scouted
+    All,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -170,10 +282,8 @@ pub fn run_scouts_for_mission(client: &mut Client<Mapping>, mission: u32, hint:
         client.scout_locations(get_locations_by_mission(client, mission), hint),
     );
 }
-pub fn run_scouts_for_secret_mission(client: &mut Client<Mapping>) {
-    archipelago_utilities::run_scouts(
-        client.scout_locations(get_secret_missions(client), CreateAsHint::No),
-    );
+pub fn run_scouts_for_secret_mission(client: &mut Client<Mapping>, hint: CreateAsHint) {
+    archipelago_utilities::run_scouts(client.scout_locations(get_secret_missions(client), hint));
 }
 
 pub fn get_locations_by_mission(client: &Client<Mapping>, mission: u32) -> Vec<Location> {
@@ -0,0 +1,151 @@
+use crate::constants::NO_MISSION;
+use crate::game_manager::with_session;
+use crate::mapping::{Goal, MAPPING, OVERLAY_INFO};
+use crate::ui::input::InputEvent;
+use crate::{check_handler, utilities};
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
+use windows::Win32::UI::Input::KeyboardAndMouse::{VK_F6, VK_F7};
+
+pub(crate) const FIRST_MISSION: u8 = 1;
+pub(crate) const LAST_MISSION: u8 = 23;
+
+/// Mission the player picked to play next, NO_MISSION if they haven't picked one
+static SELECTED_MISSION: AtomicU8 = AtomicU8::new(NO_MISSION as u8);
+
+/// Missions that can be picked, bit N is Mission #N. The game keeps no per-mission unlocks the client knows of,
+/// so this is kept with the randomizer's save instead
+static UNLOCKED_MISSIONS: AtomicU32 = AtomicU32::new(0);
+
+/// Every mission from FIRST_MISSION to LAST_MISSION
+pub(crate) const ALL_MISSIONS: u32 = (1 << (LAST_MISSION + 1)) - (1 << FIRST_MISSION);
+
+/// Goals that don't care about mission order have every mission unlocked from the start
+pub(crate) fn is_free_select() -> bool {
+    MAPPING
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|mapping| matches!(mapping.goal, Goal::All | Goal::MissionCount))
+}
+
+/// Missions that can be picked from the start for the current goal
+fn goal_unlocks() -> u32 {
+    if is_free_select() { ALL_MISSIONS } else { 0 }
+}
+
+/// Missions that can currently be picked, as stored in the save
+pub(crate) fn unlocked_missions() -> u32 {
+    UNLOCKED_MISSIONS.load(Ordering::SeqCst) | goal_unlocks()
+}
+
+fn is_unlocked(unlocked: u32, mission: u8) -> bool {
+    (FIRST_MISSION..=LAST_MISSION).contains(&mission) && unlocked & (1 << mission) != 0
+}
+
+/// Restore the unlocks a save was made with, on top of what the goal unlocks, then put the selected mission back
+/// into the session. Loading a save overwrites the session, so this runs after every load.
+pub(crate) fn apply_unlocks(saved: u32) {
+    UNLOCKED_MISSIONS.store((saved | goal_unlocks()) & ALL_MISSIONS, Ordering::SeqCst);
+    apply_selected_mission();
+}
+
+pub(crate) fn get_selected_mission() -> Option<u8> {
+    match SELECTED_MISSION.load(Ordering::SeqCst) {
+        0 => None,
+        mission => Some(mission),
+    }
+}
+
+/// The unlocked mission after/before `current`, wrapping around at either end
+fn step_mission(unlocked: u32, current: u8, forward: bool) -> Option<u8> {
+    let count = LAST_MISSION - FIRST_MISSION + 1;
+    let offset = current.clamp(FIRST_MISSION, LAST_MISSION) - FIRST_MISSION;
+    (1..=count)
+        .map(|step| {
+            let moved = if forward {
+                (offset + step) % count
+            } else {
+                (offset + count - step) % count
+            };
+            moved + FIRST_MISSION
+        })
+        .find(|mission| is_unlocked(unlocked, *mission))
+}
+
+/// Move the selection to the next/previous unlocked mission
+pub(crate) fn cycle_mission(forward: bool) {
+    let current = get_selected_mission()
+        .or_else(|| with_session(|s| s.mission).ok())
+        .unwrap_or(FIRST_MISSION);
+    let Some(selected) = step_mission(unlocked_missions(), current, forward) else {
+        return;
+    };
+    SELECTED_MISSION.store(selected, Ordering::SeqCst);
+    apply_selected_mission();
+}
+
+/// Write the selected mission into the session so it's the one that gets started.
+/// Needs to be redone after a save is loaded, as that overwrites the session.
+pub(crate) fn apply_selected_mission() {
+    let Some(selected) = get_selected_mission() else {
+        return;
+    };
+    if !is_unlocked(unlocked_missions(), selected) {
+        return;
+    }
+    log::debug!("Selected Mission #{}", selected);
+    check_handler::start_mission_at(selected);
+    if let Ok(mut info) = OVERLAY_INFO.write() {
+        info.next_mission = Some(selected);
+    }
+}
+
+/// F6/F7 pick the previous/next mission on the main menu, so this doesn't need DDMK
+pub(crate) fn handle_input(event: InputEvent) -> bool {
+    if !is_free_select() || !utilities::is_on_main_menu() {
+        return false;
+    }
+    match event {
+        InputEvent::Key(VK_F6) => cycle_mission(false),
+        InputEvent::Key(VK_F7) => cycle_mission(true),
+        _ => return false,
+    }
+    true
+}
+
+/// The game moves on to the following mission by itself, so drop the old selection
+pub(crate) fn clear_selection() {
+    SELECTED_MISSION.store(NO_MISSION as u8, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_missions_are_one_to_twenty_three() {
+        let unlocked: Vec<u8> = (0..32).filter(|m| is_unlocked(ALL_MISSIONS, *m)).collect();
+        assert_eq!(unlocked, (1..=23).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn stepping_wraps_around() {
+        assert_eq!(step_mission(ALL_MISSIONS, 1, true), Some(2));
+        assert_eq!(step_mission(ALL_MISSIONS, LAST_MISSION, true), Some(1));
+        assert_eq!(step_mission(ALL_MISSIONS, 1, false), Some(LAST_MISSION));
+        // A session that isn't on a mission yet counts as being on the first one
+        assert_eq!(step_mission(ALL_MISSIONS, 0, true), Some(2));
+    }
+
+    #[test]
+    fn stepping_skips_locked_missions() {
+        let unlocked = 1 << 3 | 1 << 9 | 1 << 20;
+        assert_eq!(step_mission(unlocked, 3, true), Some(9));
+        assert_eq!(step_mission(unlocked, 9, true), Some(20));
+        assert_eq!(step_mission(unlocked, 20, true), Some(3));
+        assert_eq!(step_mission(unlocked, 3, false), Some(20));
+        assert_eq!(step_mission(0, 3, true), None);
+        // The only unlocked mission is picked again
+        assert_eq!(step_mission(1 << 5, 5, true), Some(5));
+    }
+}
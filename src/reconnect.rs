@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Longest time to wait between two attempts
+const MAX_DELAY: Duration = Duration::from_secs(300);
+/// Backoff stops doubling after this many failed attempts
+const MAX_DOUBLINGS: u32 = 5;
+/// How long the game keeps its randomizer state without a connection before being restored
+pub const OFFLINE_GRACE_PERIOD: Duration = Duration::from_secs(600);
+
+/// Decides when to try reconnecting after the connection drops or an attempt fails
+#[derive(Debug)]
+pub struct ReconnectManager {
+    base_delay: Duration,
+    failed_attempts: u32,
+    next_attempt: Option<Instant>,
+    disconnected_since: Option<Instant>,
+}
+
+impl ReconnectManager {
+    pub fn new(interval_seconds: i32) -> Self {
+        Self {
+            base_delay: Duration::from_secs(interval_seconds.max(1) as u64),
+            failed_attempts: 0,
+            next_attempt: None,
+            disconnected_since: None,
+        }
+    }
+
+    /// True if the current failure hasn't been dealt with yet
+    pub fn is_waiting(&self) -> bool {
+        self.next_attempt.is_some()
+    }
+
+    /// Schedule the next attempt, returns how long until it happens
+    pub fn on_failure(&mut self, now: Instant) -> Duration {
+        let doublings = self.failed_attempts.min(MAX_DOUBLINGS);
+        let delay = (self.base_delay * 2u32.pow(doublings)).min(MAX_DELAY);
+        let delay = delay + jitter(delay / 5);
+        self.failed_attempts += 1;
+        self.next_attempt = Some(now + delay);
+        self.disconnected_since.get_or_insert(now);
+        delay
+    }
+
+    /// If it's time for the scheduled attempt. Clears the schedule when it is
+    pub fn should_attempt(&mut self, now: Instant) -> bool {
+        match self.next_attempt {
+            Some(at) if now >= at => {
+                self.next_attempt = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn on_connected(&mut self) {
+        self.failed_attempts = 0;
+        self.next_attempt = None;
+        self.disconnected_since = None;
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    /// When the next attempt is, if one is scheduled
+    pub fn next_attempt(&self) -> Option<Instant> {
+        self.next_attempt
+    }
+
+    /// If the connection has been gone long enough that the game should be restored
+    pub fn grace_period_expired(&self, now: Instant) -> bool {
+        self.disconnected_since
+            .is_some_and(|since| now.duration_since(since) >= OFFLINE_GRACE_PERIOD)
+    }
+}
+
+/// Random-ish extra delay so clients that dropped at the same time don't all come back at once
+fn jitter(max: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    max.mul_f64((nanos % 1000) as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Jitter adds up to a fifth on top of the delay
+    fn assert_delay(delay: Duration, expected: Duration) {
+        assert!(
+            delay >= expected && delay <= expected + expected / 5,
+            "{:?} isn't within jitter of {:?}",
+            delay,
+            expected
+        );
+    }
+
+    #[test]
+    fn delay_doubles_after_each_failure() {
+        let mut manager = ReconnectManager::new(2);
+        let now = Instant::now();
+        for doublings in 0..=MAX_DOUBLINGS {
+            let delay = manager.on_failure(now);
+            assert_delay(delay, Duration::from_secs(2 * 2u64.pow(doublings)));
+        }
+        assert_eq!(manager.attempts(), MAX_DOUBLINGS + 1);
+    }
+
+    #[test]
+    fn delay_stops_doubling() {
+        let mut manager = ReconnectManager::new(1);
+        let now = Instant::now();
+        let mut delay = Duration::ZERO;
+        for _ in 0..MAX_DOUBLINGS + 3 {
+            delay = manager.on_failure(now);
+        }
+        assert_delay(delay, Duration::from_secs(2u64.pow(MAX_DOUBLINGS)));
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let mut manager = ReconnectManager::new(60);
+        let now = Instant::now();
+        let mut delay = Duration::ZERO;
+        for _ in 0..MAX_DOUBLINGS + 1 {
+            delay = manager.on_failure(now);
+        }
+        assert_delay(delay, MAX_DELAY);
+    }
+
+    #[test]
+    fn interval_is_at_least_a_second() {
+        let mut manager = ReconnectManager::new(0);
+        assert_delay(manager.on_failure(Instant::now()), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn attempt_waits_for_the_delay() {
+        let mut manager = ReconnectManager::new(10);
+        let now = Instant::now();
+        assert!(!manager.should_attempt(now));
+        let delay = manager.on_failure(now);
+        assert!(manager.is_waiting());
+        assert!(!manager.should_attempt(now + delay - Duration::from_millis(1)));
+        assert!(manager.should_attempt(now + delay));
+        // The schedule is used up once the attempt is made
+        assert!(!manager.is_waiting());
+        assert!(!manager.should_attempt(now + delay));
+    }
+
+    #[test]
+    fn connecting_resets_the_backoff() {
+        let mut manager = ReconnectManager::new(5);
+        let now = Instant::now();
+        manager.on_failure(now);
+        manager.on_failure(now);
+        manager.on_connected();
+        assert_eq!(manager.attempts(), 0);
+        assert!(!manager.is_waiting());
+        assert!(manager.next_attempt().is_none());
+        assert_delay(manager.on_failure(now), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn grace_period_counts_from_the_first_failure() {
+        let mut manager = ReconnectManager::new(5);
+        let now = Instant::now();
+        assert!(!manager.grace_period_expired(now + OFFLINE_GRACE_PERIOD));
+        manager.on_failure(now);
+        manager.on_failure(now + Duration::from_secs(60));
+        assert!(!manager.grace_period_expired(now + OFFLINE_GRACE_PERIOD - Duration::from_secs(1)));
+        assert!(manager.grace_period_expired(now + OFFLINE_GRACE_PERIOD));
+        manager.on_connected();
+        assert!(!manager.grace_period_expired(now + OFFLINE_GRACE_PERIOD));
+    }
+}
@@ -0,0 +1,527 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+/// Where randomizer saves live, relative to the game's directory
+pub(crate) const SAVE_DIR: &str = "archipelago";
+/// Size of the game's own save blob
+pub(crate) const SAVE_LENGTH: usize = 0x5F64;
+/// Number of save slots the game shows
+pub(crate) const SAVE_SLOTS: usize = 10;
+/// Older copies kept next to each save, .1 is the newest
+const MAX_BACKUPS: usize = 3;
+const CHECKSUM_LENGTH: usize = 4;
+
+// Save container layout, all little endian:
+//   magic (8) | format version (u16) | header length (u32) | header (JSON) | game data | checksum (u32)
+// The checksum covers everything before it.
+const MAGIC: &[u8; 8] = b"DMC1APSV";
+/// Bump when the layout changes, or when the header changes in a way older versions can't read
+const FORMAT_VERSION: u16 = 1;
+const PREFIX_LENGTH: usize = MAGIC.len() + 2 + 4;
+
+/// Everything about a save that isn't the game's own data
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct SaveHeader {
+    /// Mod version that wrote the save
+    pub(crate) mod_version: String,
+    pub(crate) seed: String,
+    pub(crate) slot_name: String,
+    /// Seconds since the Unix epoch
+    pub(crate) saved_at: u64,
+    /// Received item index for each of the game's save slots
+    pub(crate) received_index: [i64; SAVE_SLOTS],
+    /// Locations checked while offline that haven't reached the server yet
+    #[serde(default)]
+    pub(crate) offline_checks: Vec<i64>,
+    /// Mission at the time of saving, 0 if unknown
+    #[serde(default)]
+    pub(crate) mission: u8,
+    /// Missions that can be picked from mission select, bit N is Mission #N
+    #[serde(default)]
+    pub(crate) unlocked_missions: u32,
+}
+
+impl SaveHeader {
+    pub(crate) fn new(seed: &str, slot_name: &str, received_index: [i64; SAVE_SLOTS]) -> Self {
+        Self {
+            mod_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: seed.to_string(),
+            slot_name: slot_name.to_string(),
+            saved_at: now(),
+            received_index,
+            offline_checks: vec![],
+            mission: 0,
+            unlocked_missions: 0,
+        }
+    }
+
+    /// Mark the header as written by this version of the mod, now
+    pub(crate) fn stamp(&mut self) {
+        self.mod_version = env!("CARGO_PKG_VERSION").to_string();
+        self.saved_at = now();
+    }
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// A save read off disk
+pub(crate) struct LoadedSave {
+    pub(crate) data: [u8; SAVE_LENGTH],
+    /// None for saves written before the container existed
+    pub(crate) header: Option<SaveHeader>,
+    /// Set when the save itself was unusable and a backup was used instead
+    pub(crate) recovered_from: Option<PathBuf>,
+}
+
+/// FNV-1a, just enough to notice a damaged file
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+fn encode(header: &SaveHeader, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let header = serde_json::to_vec(header)?;
+    let mut bytes = Vec::with_capacity(PREFIX_LENGTH + header.len() + data.len() + CHECKSUM_LENGTH);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&header);
+    bytes.extend_from_slice(data);
+    let sum = checksum(&bytes);
+    bytes.extend_from_slice(&sum.to_le_bytes());
+    Ok(bytes)
+}
+
+/// Check the file is a whole save and pull the game's data and header out of it
+fn decode(bytes: &[u8]) -> Result<([u8; SAVE_LENGTH], Option<SaveHeader>), Box<dyn Error>> {
+    if !bytes.starts_with(MAGIC) {
+        return decode_legacy(bytes).map(|data| (data, None));
+    }
+    if bytes.len() < PREFIX_LENGTH + CHECKSUM_LENGTH {
+        return Err("Save is truncated".into());
+    }
+    let (body, sum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+    if u32::from_le_bytes(sum.try_into()?) != checksum(body) {
+        return Err("Checksum does not match".into());
+    }
+    let version = u16::from_le_bytes(body[MAGIC.len()..MAGIC.len() + 2].try_into()?);
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "Save is format {} but this version of the mod only reads up to {}",
+            version, FORMAT_VERSION
+        )
+        .into());
+    }
+    let header_length = u32::from_le_bytes(body[MAGIC.len() + 2..PREFIX_LENGTH].try_into()?);
+    let header_end = PREFIX_LENGTH + header_length as usize;
+    if body.len() != header_end + SAVE_LENGTH {
+        return Err(format!(
+            "Expected {} bytes of game data, found {}",
+            SAVE_LENGTH,
+            body.len().saturating_sub(header_end)
+        )
+        .into());
+    }
+    let header = serde_json::from_slice(&body[PREFIX_LENGTH..header_end])?;
+    Ok((body[header_end..].try_into()?, Some(header)))
+}
+
+/// Saves from before the container are the game's blob, possibly followed by a checksum
+fn decode_legacy(bytes: &[u8]) -> Result<[u8; SAVE_LENGTH], Box<dyn Error>> {
+    match bytes.len() {
+        SAVE_LENGTH => Ok(bytes.try_into()?),
+        len if len == SAVE_LENGTH + CHECKSUM_LENGTH => {
+            let (data, sum) = bytes.split_at(SAVE_LENGTH);
+            if u32::from_le_bytes(sum.try_into()?) != checksum(data) {
+                return Err("Checksum does not match".into());
+            }
+            Ok(data.try_into()?)
+        }
+        len => Err(format!("Expected {} bytes, found {}", SAVE_LENGTH, len).into()),
+    }
+}
+
+/// Characters Windows doesn't allow in file names, on top of control characters
+const INVALID_NAME_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Make sure a seed or slot name from somewhere untrusted can't take a save path outside
+/// [`SAVE_DIR`] or make it an invalid file name
+pub(crate) fn check_name_part(kind: &str, part: &str) -> Result<(), Box<dyn Error>> {
+    if part.is_empty() {
+        return Err(format!("Save has no {}", kind).into());
+    }
+    if part.contains("..")
+        || part
+            .chars()
+            .any(|c| c.is_control() || INVALID_NAME_CHARS.contains(&c))
+    {
+        return Err(format!("Save has an invalid {}: {:?}", kind, part).into());
+    }
+    Ok(())
+}
+
+/// Where the save for a seed and slot lives
+pub(crate) fn save_path(seed: &str, slot_name: &str) -> PathBuf {
+    Path::new(SAVE_DIR).join(format!("dmc1_{}_{}.sav", seed, slot_name))
+}
+
+/// Read a single file without falling back to backups
+pub(crate) fn read_file(
+    path: &Path,
+) -> Result<([u8; SAVE_LENGTH], Option<SaveHeader>), Box<dyn Error>> {
+    decode(&fs::read(path)?)
+}
+
+/// Backups that exist for a save, newest first
+pub(crate) fn existing_backups(path: &Path) -> Vec<PathBuf> {
+    (1..=MAX_BACKUPS)
+        .map(|number| backup_path(path, number))
+        .filter(|backup| backup.exists())
+        .collect()
+}
+
+fn backup_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", number));
+    PathBuf::from(name)
+}
+
+/// Shift the backups along by one, then copy the current save in as the newest. A damaged save is
+/// never backed up so it can't push out a good copy
+fn rotate_backups(path: &Path) -> Result<(), Box<dyn Error>> {
+    match fs::read(path) {
+        Ok(bytes) if decode(&bytes).is_ok() => {}
+        Ok(_) => {
+            log::warn!("Not backing up damaged save {}", path.display());
+            return Ok(());
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    }
+    for number in (1..MAX_BACKUPS).rev() {
+        let from = backup_path(path, number);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, number + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Move a save and its backups to another path, giving their headers a new slot name. Files from
+/// before the container have no header and are moved as they are. Never overwrites another save
+pub(crate) fn move_save(from: &Path, to: &Path, slot_name: &str) -> Result<(), Box<dyn Error>> {
+    fs::metadata(from)?;
+    if to.exists() || !existing_backups(to).is_empty() {
+        return Err(format!("{} already exists", to.display()).into());
+    }
+    let files = std::iter::once((from.to_path_buf(), to.to_path_buf())).chain(
+        (1..=MAX_BACKUPS).map(|number| (backup_path(from, number), backup_path(to, number))),
+    );
+    for (source, destination) in files {
+        if !source.exists() {
+            continue;
+        }
+        match read_file(&source) {
+            Ok((data, Some(mut header))) => {
+                header.slot_name = slot_name.to_string();
+                write_atomically(&destination, &encode(&header, &data)?)?;
+                fs::remove_file(&source)?;
+            }
+            _ => fs::rename(&source, &destination)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write the file to a temporary first and move it over, so a crash mid-write leaves the old file intact
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    {
+        let mut file = File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+pub(crate) fn write_save(
+    path: &Path,
+    header: &SaveHeader,
+    data: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    if data.len() != SAVE_LENGTH {
+        return Err(format!(
+            "Save data is {} bytes, expected {}",
+            data.len(),
+            SAVE_LENGTH
+        )
+        .into());
+    }
+    if let Err(err) = rotate_backups(path) {
+        // Still worth saving without a backup
+        log::error!("Failed to back up {}: {}", path.display(), err);
+    }
+    write_atomically(path, &encode(header, data)?)
+}
+
+/// Read a save, falling back to the newest good backup if it's missing or damaged. Gives back
+/// a NotFound io::Error when there's no save at all
+pub(crate) fn read_save(path: &Path) -> Result<LoadedSave, Box<dyn Error>> {
+    let main_error: Box<dyn Error> = match fs::read(path) {
+        Ok(bytes) => match decode(&bytes) {
+            Ok((data, header)) => {
+                return Ok(LoadedSave {
+                    data,
+                    header,
+                    recovered_from: None,
+                });
+            }
+            Err(err) => {
+                log::error!("Save {} is damaged: {}", path.display(), err);
+                err
+            }
+        },
+        Err(err) => err.into(),
+    };
+    for number in 1..=MAX_BACKUPS {
+        let backup = backup_path(path, number);
+        let Ok(bytes) = fs::read(&backup) else {
+            continue;
+        };
+        match decode(&bytes) {
+            Ok((data, header)) => {
+                log::warn!("Recovered save from {}", backup.display());
+                return Ok(LoadedSave {
+                    data,
+                    header,
+                    recovered_from: Some(backup),
+                });
+            }
+            Err(err) => log::error!("Backup {} is damaged: {}", backup.display(), err),
+        }
+    }
+    Err(main_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save path in an empty directory of its own
+    fn temp_save(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dmc1_save_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("dmc1_seed_Dante.sav")
+    }
+
+    /// Game data filled with one byte, so saves can be told apart
+    fn data(fill: u8) -> [u8; SAVE_LENGTH] {
+        [fill; SAVE_LENGTH]
+    }
+
+    fn header() -> SaveHeader {
+        SaveHeader::new("seed", "Dante", [0; SAVE_SLOTS])
+    }
+
+    fn fill_of(path: &Path) -> u8 {
+        read_file(path).unwrap().0[0]
+    }
+
+    #[test]
+    fn encoded_save_round_trips() {
+        let mut original = header();
+        original.offline_checks = vec![1000, 1001];
+        original.unlocked_missions = 0b110;
+        let (game_data, header) = decode(&encode(&original, &data(7)).unwrap()).unwrap();
+        assert_eq!(game_data, data(7));
+        let header = header.unwrap();
+        assert_eq!(header.seed, "seed");
+        assert_eq!(header.slot_name, "Dante");
+        assert_eq!(header.offline_checks, vec![1000, 1001]);
+        assert_eq!(header.unlocked_missions, 0b110);
+    }
+
+    #[test]
+    fn flipped_byte_fails_the_checksum() {
+        let bytes = encode(&header(), &data(7)).unwrap();
+        for position in [MAGIC.len() + 1, PREFIX_LENGTH + 1, bytes.len() - 10] {
+            let mut damaged = bytes.clone();
+            damaged[position] ^= 0xFF;
+            assert!(decode(&damaged).is_err(), "byte {}", position);
+        }
+    }
+
+    /// Re-checksum a save after changing it, so only the change itself can be rejected
+    fn resum(bytes: &mut Vec<u8>) {
+        bytes.truncate(bytes.len() - CHECKSUM_LENGTH);
+        let sum = checksum(bytes);
+        bytes.extend_from_slice(&sum.to_le_bytes());
+    }
+
+    #[test]
+    fn newer_format_is_rejected() {
+        let mut bytes = encode(&header(), &data(7)).unwrap();
+        bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        resum(&mut bytes);
+        assert!(decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn header_longer_than_the_file_is_rejected() {
+        let mut bytes = encode(&header(), &data(7)).unwrap();
+        bytes[MAGIC.len() + 2..PREFIX_LENGTH].copy_from_slice(&u32::MAX.to_le_bytes());
+        resum(&mut bytes);
+        assert!(decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn legacy_saves_have_no_header() {
+        let raw = data(7);
+        let (data, header) = decode(&raw).unwrap();
+        assert_eq!(data, raw);
+        assert!(header.is_none());
+
+        let mut summed = raw.to_vec();
+        summed.extend_from_slice(&checksum(&raw).to_le_bytes());
+        let (data, header) = decode(&summed).unwrap();
+        assert_eq!(data, raw);
+        assert!(header.is_none());
+
+        let last = summed.len() - 1;
+        summed[last] ^= 0xFF;
+        assert!(decode(&summed).is_err());
+    }
+
+    #[test]
+    fn backups_rotate_newest_first() {
+        let path = temp_save("rotate");
+        for fill in 1..=4 {
+            write_save(&path, &header(), &data(fill)).unwrap();
+        }
+        assert_eq!(fill_of(&path), 4);
+        assert_eq!(existing_backups(&path).len(), MAX_BACKUPS);
+        assert_eq!(fill_of(&backup_path(&path, 1)), 3);
+        assert_eq!(fill_of(&backup_path(&path, 2)), 2);
+        assert_eq!(fill_of(&backup_path(&path, 3)), 1);
+        // A fifth save pushes the oldest out
+        write_save(&path, &header(), &data(5)).unwrap();
+        assert_eq!(fill_of(&backup_path(&path, 1)), 4);
+        assert_eq!(fill_of(&backup_path(&path, 3)), 2);
+        assert!(!backup_path(&path, 4).exists());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn damaged_save_is_not_backed_up() {
+        let path = temp_save("damaged");
+        write_save(&path, &header(), &data(1)).unwrap();
+        write_save(&path, &header(), &data(2)).unwrap();
+        fs::write(&path, b"not a save").unwrap();
+        write_save(&path, &header(), &data(3)).unwrap();
+        assert_eq!(fill_of(&path), 3);
+        assert_eq!(fill_of(&backup_path(&path, 1)), 1);
+        assert!(!backup_path(&path, 2).exists());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn damaged_save_recovers_from_newest_good_backup() {
+        let path = temp_save("recover");
+        for fill in 1..=3 {
+            write_save(&path, &header(), &data(fill)).unwrap();
+        }
+        fs::write(&path, b"not a save").unwrap();
+        fs::write(backup_path(&path, 1), b"not a backup either").unwrap();
+        let loaded = read_save(&path).unwrap();
+        assert_eq!(loaded.data[0], 1);
+        assert_eq!(loaded.header.unwrap().seed, "seed");
+        assert_eq!(loaded.recovered_from, Some(backup_path(&path, 2)));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn good_save_is_not_recovered() {
+        let path = temp_save("good");
+        write_save(&path, &header(), &data(1)).unwrap();
+        write_save(&path, &header(), &data(2)).unwrap();
+        let loaded = read_save(&path).unwrap();
+        assert_eq!(loaded.data[0], 2);
+        assert_eq!(loaded.recovered_from, None);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn missing_save_is_not_found() {
+        let path = temp_save("missing");
+        let err = match read_save(&path) {
+            Ok(_) => panic!("Read a save that doesn't exist"),
+            Err(err) => err,
+        };
+        assert_eq!(
+            err.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::NotFound)
+        );
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn truncated_save_is_an_error() {
+        let path = temp_save("truncated");
+        write_save(&path, &header(), &data(1)).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        for length in [0, 4, MAGIC.len(), PREFIX_LENGTH, bytes.len() - 1] {
+            fs::write(&path, &bytes[..length]).unwrap();
+            assert!(read_file(&path).is_err(), "{} bytes", length);
+        }
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn normal_names_are_allowed() {
+        assert!(check_name_part("seed", "12345678901234567890").is_ok());
+        assert!(check_name_part("slot name", "Dante 2").is_ok());
+        assert!(check_name_part("slot name", "Son of Sparda.").is_ok());
+    }
+
+    #[test]
+    fn path_escapes_are_rejected() {
+        for name in ["..", "../saves", "a/b", "a\\b", "C:evil", "x..y"] {
+            assert!(check_name_part("slot name", name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn invalid_file_names_are_rejected() {
+        for name in [
+            "",
+            "what?",
+            "a*b",
+            "<Dante>",
+            "a|b",
+            "\"quoted\"",
+            "tab\there",
+        ] {
+            assert!(check_name_part("slot name", name).is_err(), "{:?}", name);
+        }
+    }
+}
@@ -1,37 +1,112 @@
 use crate::archipelago::CONNECTED;
-use crate::game_manager::{ARCHIPELAGO_DATA, ArchipelagoData};
+use crate::game_memory::{DMC1Memory, GameMemory};
+use crate::mapping::Mapping;
+use crate::save_file::{SAVE_LENGTH, SAVE_SLOTS, SaveHeader};
+use crate::ui::overlay;
+use crate::ui::overlay::{MessageSegment, MessageType, OverlayMessage};
 use crate::utilities::DMC1_ADDRESS;
-use crate::{AP_CORE, archipelago, create_hook};
+use crate::{AP_CORE, archipelago, create_hook, mission_select, save_file};
+use archipelago_rs::Client;
 use minhook::MH_STATUS;
 use minhook::MinHook;
-use randomizer_utilities::item_sync::{CURRENT_INDEX, SlotSyncInfo};
+use randomizer_utilities::item_sync::{CURRENT_INDEX, OFFLINE_CHECKS};
+use randomizer_utilities::ui::font_handler::{FontColorCB, RED, YELLOW};
 use randomizer_utilities::{item_sync, read_data_from_address};
 use std::error::Error;
+use std::io;
 use std::io::ErrorKind;
+use std::path::Path;
 use std::ptr::write;
 use std::sync::atomic::Ordering;
-use std::sync::{OnceLock, RwLock};
-use std::{fs, io};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::Duration;
 
 /// Pointer to where save file is in memory
 const SAVE_FILE_PTR: usize = 0x60afc8;
 static SAVE_DATA: RwLock<[u8; SAVE_LENGTH]> = RwLock::new([0; SAVE_LENGTH]);
-const SAVE_LENGTH: usize = 0x5F64;
+/// Header of the connected slot's save, kept up to date as slots are saved and written out with the game's data
+static SAVE_HEADER: RwLock<Option<SaveHeader>> = RwLock::new(None);
 
 pub fn get_save_path() -> Result<String, Box<dyn Error>> {
     if let Ok(core) = AP_CORE.get().unwrap().as_ref().lock()
-        && let Some(client) = core.connection.client()
+        && let Some(client) = core.client()
     {
-        Ok(format!(
-            "archipelago/dmc1_{}_{}.sav",
-            client.seed_name(),
-            client.this_player().name()
-        ))
+        Ok(
+            save_file::save_path(client.seed_name(), &client.this_player().name())
+                .to_string_lossy()
+                .into_owned(),
+        )
     } else {
         Err("Connection unavailable".into())
     }
 }
 
+pub(crate) fn header_matches(header: &SaveHeader, client: &Client<Mapping>) -> bool {
+    header.seed == client.seed_name() && header.slot_name == client.this_player().name()
+}
+
+/// Header for a save from before the container. Indices and offline checks used to live in the
+/// separate item sync file, so they're brought over from there
+fn legacy_save_header(client: &Client<Mapping>) -> SaveHeader {
+    let mut header = SaveHeader::new(
+        client.seed_name(),
+        &client.this_player().name(),
+        [0; SAVE_SLOTS],
+    );
+    if let Ok(sync_data) = item_sync::read_save_data()
+        && let Some(sync_info) = sync_data.room_sync_info.get(&item_sync::get_sync_file_key(
+            client.seed_name(),
+            client.this_player().name().into(),
+        ))
+    {
+        for (slot, index) in sync_info.sync_index.iter().take(SAVE_SLOTS).enumerate() {
+            header.received_index[slot] = *index;
+        }
+        header.offline_checks = sync_info.offline_checks.clone();
+    }
+    header
+}
+
+/// Run something against the connected slot's save header, starting a fresh one if there isn't one
+/// for this slot yet
+fn with_save_header<F, R>(f: F) -> Result<R, Box<dyn Error>>
+where
+    F: FnOnce(&mut SaveHeader) -> R,
+{
+    let Ok(core) = AP_CORE.get().unwrap().as_ref().lock() else {
+        return Err("Unable to lock core".into());
+    };
+    let Some(client) = core.client() else {
+        return Err("Connection unavailable".into());
+    };
+    let mut current = SAVE_HEADER.write().map_err(|err| err.to_string())?;
+    if current
+        .as_ref()
+        .is_some_and(|header| !header_matches(header, client))
+    {
+        *current = None;
+    }
+    let header = current.get_or_insert_with(|| {
+        SaveHeader::new(
+            client.seed_name(),
+            &client.this_player().name(),
+            [0; SAVE_SLOTS],
+        )
+    });
+    Ok(f(header))
+}
+
+/// Let the player know something happened to their save, these shouldn't only end up in the log
+fn show_save_message(text: String, color: FontColorCB) {
+    overlay::add_message(OverlayMessage::new(
+        vec![MessageSegment::new(text, color)],
+        Duration::from_secs(8),
+        0.0,
+        0.0,
+        MessageType::Notification,
+    ));
+}
+
 pub fn setup_save_hooks() -> Result<(), MH_STATUS> {
     log::debug!("Setting up save file related hooks");
     unsafe {
@@ -67,6 +142,7 @@ pub(crate) fn add_hooks_to_list(addrs: &mut Vec<usize>) {
     addrs.push(LOAD_GAME_ADDR);
     addrs.push(SAVE_GAME_ADDR);
     addrs.push(LOAD_SLOT_ADDR);
+    addrs.push(SAVE_SLOT_ADDR);
 }
 
 pub const SAVE_GAME_ADDR: usize = 0x443c0;
@@ -82,10 +158,23 @@ fn new_save_game(param_1: usize) {
             //let save_file_ptr = (param_1 + 0x70) as *const usize;
             let save_file = (param_1 + 0x70) as *const u8;
 
-            let data = std::slice::from_raw_parts(save_file, SAVE_LENGTH).to_vec();
+            let data = std::slice::from_raw_parts(save_file, SAVE_LENGTH);
 
-            fs::write(get_save_path().expect("Unable to get save path"), data)
-                .expect("Unable to save game");
+            // Doesn't rely on save_to_slot having run first, the slot's progress goes in here as well
+            let save_index = current_save_slot();
+            let header = with_save_header(|header| {
+                record_slot_progress(header, save_index);
+                header.stamp();
+                header.mission = DMC1Memory.mission();
+                header.unlocked_missions = mission_select::unlocked_missions();
+                header.clone()
+            });
+            if let Err(err) = get_save_path()
+                .and_then(|path| save_file::write_save(Path::new(&path), &header?, data))
+            {
+                log::error!("Unable to save game: {}", err);
+                show_save_message(format!("Unable to save game: {}", err), RED);
+            }
         }
     }
 }
@@ -121,10 +210,12 @@ fn new_load_game(param_1: usize, param_2: usize) -> i64 {
                         ErrorKind::NotFound => {}
                         _ => {
                             log::error!("Error getting save data: {}", err);
+                            show_save_message(format!("Unable to load save: {}", err), RED);
                         }
                     },
                     Err(failed) => {
                         log::error!("Error getting save data: {}", failed);
+                        show_save_message(format!("Unable to load save: {}", failed), RED);
                     }
                 }
                 -1
@@ -142,7 +233,53 @@ fn new_load_game(param_1: usize, param_2: usize) -> i64 {
 
 /// Get the save data to store in the SAVE_DATA global
 fn get_save_data() -> Result<(), Box<dyn Error>> {
-    *SAVE_DATA.write()? = <[u8; 24420]>::try_from(fs::read(get_save_path()?)?).unwrap();
+    let path = get_save_path()?;
+    let loaded = match save_file::read_save(Path::new(&path)) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            // Don't carry another seed's header over to this one
+            *SAVE_HEADER.write().map_err(|err| err.to_string())? = None;
+            return Err(err);
+        }
+    };
+    let header = match loaded.header {
+        Some(header) => {
+            log::debug!(
+                "Save for {} ({}) written by mod version {}",
+                header.slot_name,
+                header.seed,
+                header.mod_version
+            );
+            header
+        }
+        None => {
+            // From before the container, rewrite it now so it has a header. The old file becomes the newest backup
+            log::info!("Upgrading save {}", path);
+            let header = match AP_CORE.get().unwrap().as_ref().lock() {
+                Ok(core) => core.client().map(legacy_save_header),
+                Err(err) => {
+                    log::error!("Unable to lock core: {}", err);
+                    None
+                }
+            }
+            .ok_or("Connection unavailable")?;
+            if let Err(err) = save_file::write_save(Path::new(&path), &header, &loaded.data) {
+                log::error!("Failed to upgrade save: {}", err);
+            }
+            header
+        }
+    };
+    *SAVE_HEADER.write().map_err(|err| err.to_string())? = Some(header);
+    if let Some(backup) = loaded.recovered_from {
+        show_save_message(
+            format!(
+                "Save was damaged, loaded backup {}",
+                backup.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            YELLOW,
+        );
+    }
+    *SAVE_DATA.write()? = loaded.data;
     Ok(())
 }
 
@@ -158,93 +295,184 @@ fn load_save_slot(param_1: usize) {
     } else {
         panic!("Load save slot not found");
     }
+    let header = SAVE_HEADER.read().ok().and_then(|header| header.clone());
+    mission_select::apply_unlocks(
+        header
+            .as_ref()
+            .map(|header| header.unlocked_missions)
+            .unwrap_or_default(),
+    );
     match AP_CORE.get().unwrap().lock() {
         Ok(mut core) => {
-            let client = core.connection.client_mut().unwrap();
-            match item_sync::read_save_data() {
-                Ok(sync_data) => {
-                    match sync_data.room_sync_info.get(&item_sync::get_sync_file_key(
-                        client.seed_name(),
-                        client.this_player().name().into(),
-                    )) {
-                        None => {
-                            // Doesn't exist so 0
-                            CURRENT_INDEX.store(0, Ordering::SeqCst);
-                        }
-                        Some(arr) => {
-                            CURRENT_INDEX
-                                .store(arr.sync_index[save_index as usize], Ordering::SeqCst);
-                            *ARCHIPELAGO_DATA.write().unwrap() = ArchipelagoData::default();
-                            if let Err(e) = archipelago::handle_received_items_packet(
-                                arr.sync_index[save_index as usize] as usize,
-                                client,
-                            ) {
-                                log::error!("Failed to handle received items: {:?}", e);
-                            }
+            let Some(client) = core.client_mut() else {
+                log::error!("Not connected, unable to restore received items");
+                return;
+            };
+            let resume_from = checked_received_index(header.as_ref(), save_index as usize, client);
+            let index = match resume_from {
+                ResumeFrom::Index(index) | ResumeFrom::Resync(index) => index,
+            };
+            CURRENT_INDEX.store(index, Ordering::SeqCst);
+            if let Err(e) = archipelago::reset_item_data(&DMC1Memory, client) {
+                log::error!("Failed to reset item data: {:?}", e);
+            }
+            let resync = matches!(resume_from, ResumeFrom::Resync(_));
+            if !resync
+                && let Err(e) =
+                    archipelago::handle_received_items_packet(&DMC1Memory, index as usize, client)
+            {
+                log::error!("Failed to handle received items: {:?}", e);
+            }
+            // Locations checked while offline that the server might not know about yet
+            if let Some(header) = header
+                && header_matches(&header, client)
+                && !header.offline_checks.is_empty()
+            {
+                if let Ok(mut offline_checks) = OFFLINE_CHECKS.lock() {
+                    for location in header.offline_checks {
+                        if !offline_checks.contains(&location) {
+                            offline_checks.push(location);
                         }
                     }
                 }
-                Err(err) => {
-                    log::error!("Error getting sync data: {}", err);
+                if let Err(e) = item_sync::send_offline_checks(client) {
+                    log::error!("Failed to send offline checks: {:?}", e);
                 }
             }
+            if resync {
+                await_resync(index);
+                core.resync();
+            }
         }
         Err(err) => {
-            log::error!("Error locking core while writing sync data: {}", err);
+            log::error!("Error locking core while loading save slot: {}", err);
         }
     }
 }
 
+/// Where a loaded save slot picks up the received items from
+enum ResumeFrom {
+    /// The client has everything up to this index
+    Index(i64),
+    /// The save has seen more items than the client has, they need to come from the server again
+    Resync(i64),
+}
+
+/// Received item index a save slot is waiting on the server to catch up to
+static AWAITING_RESYNC: Mutex<Option<i64>> = Mutex::new(None);
+
+/// Received item index to resume the save slot from. Items the server has already sent are all
+/// kept by the client, a save that's further along than that means the client's list is behind
+fn checked_received_index(
+    header: Option<&SaveHeader>,
+    save_index: usize,
+    client: &Client<Mapping>,
+) -> ResumeFrom {
+    let Some(header) = header else {
+        // New save
+        return ResumeFrom::Index(0);
+    };
+    if !header_matches(header, client) {
+        log::warn!(
+            "Save belongs to {} ({}), not the connected slot. Receiving all items again",
+            header.slot_name,
+            header.seed
+        );
+        show_save_message(
+            "Save is from another slot, receiving all items again".to_string(),
+            YELLOW,
+        );
+        return ResumeFrom::Index(0);
+    }
+    let index = header.received_index.get(save_index).copied().unwrap_or(0);
+    let received = client.received_items().len() as i64;
+    if index < 0 {
+        log::warn!(
+            "Save slot {} has an invalid received item index {}. Receiving all items again",
+            save_index,
+            index
+        );
+        return ResumeFrom::Index(0);
+    }
+    if index > received {
+        log::warn!(
+            "Save slot {} expects {} received items but the client has {}. Requesting them again",
+            save_index,
+            index,
+            received
+        );
+        show_save_message(
+            "Save is ahead of the client, requesting items from the server".to_string(),
+            YELLOW,
+        );
+        return ResumeFrom::Resync(index);
+    }
+    ResumeFrom::Index(index)
+}
+
+/// Have the next received items from the server checked against the index a save slot expects
+pub(crate) fn await_resync(index: i64) {
+    match AWAITING_RESYNC.lock() {
+        Ok(mut awaiting) => *awaiting = Some(index),
+        Err(err) => log::error!("Unable to wait for resync: {}", err),
+    }
+}
+
+/// Run after the server sends received items, finishes off a resync started by loading a save slot.
+/// If the server still has fewer items than the save expects, the save doesn't belong to this room
+pub(crate) fn finish_resync<M: GameMemory>(
+    memory: &M,
+    client: &mut Client<Mapping>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(index) = AWAITING_RESYNC.lock()?.take() else {
+        return Ok(());
+    };
+    let received = client.received_items().len() as i64;
+    if index <= received {
+        log::info!("Server sent {} items, save slot is back in sync", received);
+        return Ok(());
+    }
+    log::warn!(
+        "Save expects {} received items but the server has sent {}. Receiving all items again",
+        index,
+        received
+    );
+    show_save_message(
+        "Save doesn't match the server, receiving all items again".to_string(),
+        YELLOW,
+    );
+    CURRENT_INDEX.store(0, Ordering::SeqCst);
+    archipelago::handle_received_items_packet(memory, 0, client)
+}
+
 pub const SAVE_SLOT_ADDR: usize = 0x255220;
 pub static ORIGINAL_SAVE_SLOT: OnceLock<unsafe extern "C" fn(usize)> = OnceLock::new();
 fn save_to_slot(param_1: usize) {
-    let save_index = read_data_from_address::<u8>(
-        read_data_from_address::<usize>(*DMC1_ADDRESS + SAVE_FILE_PTR) + 6,
-    );
+    let save_index = current_save_slot();
     if let Some(orig) = ORIGINAL_SAVE_SLOT.get() {
         unsafe {
             orig(param_1);
         }
     }
     log::debug!("Saving to slot {}", save_index);
-    match AP_CORE.get().unwrap().lock() {
-        Ok(core) => {
-            let client = core.connection.client().unwrap();
-            match item_sync::read_save_data() {
-                Ok(mut sync_data) => {
-                    let key = item_sync::get_sync_file_key(
-                        client.seed_name(),
-                        client.this_player().name().into(),
-                    );
-                    match sync_data.room_sync_info.get_mut(&key) {
-                        None => {
-                            // Doesn't exist, need to add
-                            let mut sync_info = SlotSyncInfo::default();
-                            sync_info.sync_index[save_index as usize] =
-                                CURRENT_INDEX.load(Ordering::SeqCst);
-                            sync_info.offline_checks =
-                                item_sync::OFFLINE_CHECKS.lock().unwrap().clone();
-                            sync_data.room_sync_info.insert(key, sync_info);
-                        }
-                        Some(sync_info) => {
-                            sync_info.sync_index[save_index as usize] =
-                                CURRENT_INDEX.load(Ordering::SeqCst);
-                            sync_info.offline_checks =
-                                item_sync::OFFLINE_CHECKS.lock().unwrap().clone();
-                        }
-                    }
-                    item_sync::OFFLINE_CHECKS.lock().unwrap().clear();
-                    if let Err(e) = item_sync::write_sync_data_file(sync_data) {
-                        log::error!("Error writing sync data: {}", e);
-                    }
-                }
-                Err(err) => {
-                    log::error!("Error getting sync data: {}", err);
-                }
-            }
-        }
-        Err(err) => {
-            log::error!("Error locking core while writing sync data: {}", err);
-        }
+    if let Err(err) = with_save_header(|header| record_slot_progress(header, save_index)) {
+        log::error!("Error updating save header: {}", err);
+    }
+}
+
+/// Save slot the game is working with
+fn current_save_slot() -> u8 {
+    read_data_from_address::<u8>(read_data_from_address::<usize>(*DMC1_ADDRESS + SAVE_FILE_PTR) + 6)
+}
+
+/// Put the received item index and anything still unsent in the header for a save slot
+fn record_slot_progress(header: &mut SaveHeader, save_index: u8) {
+    if let Some(index) = header.received_index.get_mut(save_index as usize) {
+        *index = CURRENT_INDEX.load(Ordering::SeqCst);
     }
+    // Anything still unsent stays with the save until the server has it
+    header.offline_checks = OFFLINE_CHECKS
+        .lock()
+        .map(|checks| checks.clone())
+        .unwrap_or_default();
 }
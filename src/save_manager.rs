@@ -0,0 +1,374 @@
+use crate::AP_CORE;
+use crate::save_file;
+use crate::save_file::{SAVE_DIR, SaveHeader};
+use crate::save_handler::header_matches;
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+/// Exported saves end up here, and files dropped in the import folder show up to be imported
+const EXPORT_DIR: &str = "archipelago/export";
+const IMPORT_DIR: &str = "archipelago/import";
+/// Deleted saves are moved here instead of being removed, in case it was the wrong one
+const DELETED_DIR: &str = "archipelago/deleted";
+
+/// A save in the save directory
+#[derive(Debug, Clone)]
+pub(crate) struct SaveEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) seed: String,
+    pub(crate) slot_name: String,
+    /// Seconds since the Unix epoch
+    pub(crate) last_played: u64,
+    /// 0 if the save doesn't say
+    pub(crate) mission: u8,
+    /// Full header, None for saves from before the container or ones that couldn't be read
+    pub(crate) header: Option<SaveHeader>,
+    /// Why the save couldn't be read
+    pub(crate) error: Option<String>,
+}
+
+#[derive(Default)]
+pub(crate) struct SaveManager {
+    pub(crate) saves: Vec<SaveEntry>,
+    pub(crate) imports: Vec<PathBuf>,
+    /// Result of the last action
+    pub(crate) status: String,
+    /// Save waiting on a second press of delete
+    pub(crate) pending_delete: Option<PathBuf>,
+    /// Save the rename box is open for
+    pub(crate) pending_rename: Option<PathBuf>,
+    /// New slot name being typed in
+    pub(crate) rename_to: String,
+    loaded: bool,
+}
+
+pub(crate) static SAVE_MANAGER: LazyLock<Mutex<SaveManager>> =
+    LazyLock::new(|| Mutex::new(SaveManager::default()));
+
+/// Look through the save and import folders again. Done once when first shown and after every action,
+/// not every frame
+pub(crate) fn refresh() {
+    let saves = list_saves();
+    let imports = list_files(Path::new(IMPORT_DIR));
+    if let Ok(mut manager) = SAVE_MANAGER.lock() {
+        manager.saves = saves;
+        manager.imports = imports;
+    }
+}
+
+pub(crate) fn refresh_if_needed() {
+    if let Ok(mut manager) = SAVE_MANAGER.lock()
+        && !manager.loaded
+    {
+        manager.loaded = true;
+        thread::spawn(refresh);
+    }
+}
+
+fn set_status(status: String) {
+    log::info!("{}", status);
+    if let Ok(mut manager) = SAVE_MANAGER.lock() {
+        manager.status = status;
+    }
+}
+
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Every archipelago/dmc1_*.sav, most recently played first
+pub(crate) fn list_saves() -> Vec<SaveEntry> {
+    let mut saves: Vec<SaveEntry> = list_files(Path::new(SAVE_DIR))
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("dmc1_") && name.ends_with(".sav"))
+        })
+        .map(read_entry)
+        .collect();
+    saves.sort_by_key(|save| Reverse(save.last_played));
+    saves
+}
+
+fn read_entry(path: PathBuf) -> SaveEntry {
+    // Older saves only have the file name to go on, seeds never have an underscore in them
+    let (seed, slot_name) = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.strip_prefix("dmc1_"))
+        .and_then(|rest| rest.split_once('_'))
+        .map(|(seed, slot)| (seed.to_string(), slot.to_string()))
+        .unwrap_or_default();
+    let modified = fs::metadata(&path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    match save_file::read_file(&path) {
+        Ok((_, Some(header))) => SaveEntry {
+            seed: header.seed.clone(),
+            slot_name: header.slot_name.clone(),
+            last_played: header.saved_at,
+            mission: header.mission,
+            header: Some(header),
+            error: None,
+            path,
+        },
+        Ok((_, None)) => SaveEntry {
+            path,
+            seed,
+            slot_name,
+            last_played: modified,
+            mission: 0,
+            header: None,
+            error: None,
+        },
+        Err(err) => SaveEntry {
+            path,
+            seed,
+            slot_name,
+            last_played: modified,
+            mission: 0,
+            header: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+/// The connected slot's save shouldn't be touched from here, the game would just write it back
+fn is_connected_save(entry: &SaveEntry) -> bool {
+    let Some(Ok(core)) = AP_CORE.get().map(|core| core.lock()) else {
+        return false;
+    };
+    core.client().is_some_and(|client| match &entry.header {
+        Some(header) => header_matches(header, client),
+        None => entry.seed == client.seed_name() && entry.slot_name == client.this_player().name(),
+    })
+}
+
+/// Copy a save to the export folder. The save file carries everything it needs so the copy is all
+/// that's needed to move it to another machine
+pub(crate) fn export_save(entry: &SaveEntry) -> Result<PathBuf, Box<dyn Error>> {
+    let (_, header) = save_file::read_file(&entry.path)?;
+    let Some(header) = header else {
+        return Err("Load this save once so it gets upgraded before exporting it".into());
+    };
+    fs::create_dir_all(EXPORT_DIR)?;
+    let export = Path::new(EXPORT_DIR).join(format!(
+        "dmc1_{}_{}_{}.sav",
+        header.seed, header.slot_name, header.saved_at
+    ));
+    fs::copy(&entry.path, &export)?;
+    Ok(export)
+}
+
+/// Bring in an exported save. An existing save for the same seed and slot becomes a backup rather
+/// than being overwritten
+pub(crate) fn import_save(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let (data, header) = save_file::read_file(path)?;
+    let Some(header) = header else {
+        return Err("Not an exported save, it has no header".into());
+    };
+    save_file::check_name_part("seed", &header.seed)?;
+    save_file::check_name_part("slot name", &header.slot_name)?;
+    let destination = save_file::save_path(&header.seed, &header.slot_name);
+    let entry = SaveEntry {
+        path: destination.clone(),
+        seed: header.seed.clone(),
+        slot_name: header.slot_name.clone(),
+        last_played: header.saved_at,
+        mission: header.mission,
+        header: Some(header.clone()),
+        error: None,
+    };
+    if is_connected_save(&entry) {
+        return Err("Can't import over the save for the connected slot, disconnect first".into());
+    }
+    save_file::write_save(&destination, &header, &data)?;
+    Ok(destination)
+}
+
+/// Move a save and its backups to the deleted folder
+pub(crate) fn delete_save(entry: &SaveEntry) -> Result<(), Box<dyn Error>> {
+    if is_connected_save(entry) {
+        return Err("Can't delete the save for the connected slot, disconnect first".into());
+    }
+    move_to_folder(&entry.path, Path::new(DELETED_DIR))
+}
+
+/// Move a save and its backups into a folder, stamped with the time so the same save can go in
+/// more than once
+fn move_to_folder(path: &Path, folder: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(folder)?;
+    let stamp = save_file::now();
+    for file in std::iter::once(path.to_path_buf()).chain(save_file::existing_backups(path)) {
+        if let Some(name) = file.file_name() {
+            let mut moved = name.to_owned();
+            moved.push(format!(".{}", stamp));
+            fs::rename(&file, folder.join(moved))?;
+        }
+    }
+    Ok(())
+}
+
+/// Give a save a new slot name, so it can be played on a slot with a different name. Its backups
+/// move along with it and an existing save is never overwritten
+pub(crate) fn rename_save(entry: &SaveEntry, slot_name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    save_file::check_name_part("seed", &entry.seed)?;
+    save_file::check_name_part("slot name", slot_name)?;
+    if is_connected_save(entry) {
+        return Err("Can't rename the save for the connected slot, disconnect first".into());
+    }
+    // Stays in the same folder as the original
+    let name = save_file::save_path(&entry.seed, slot_name);
+    let destination = entry
+        .path
+        .with_file_name(name.file_name().unwrap_or_default());
+    save_file::move_save(&entry.path, &destination, slot_name)?;
+    Ok(destination)
+}
+
+/// Run an action off the render thread, then report how it went and refresh the lists
+pub(crate) fn run_action<F>(action: F)
+where
+    F: FnOnce() -> Result<String, Box<dyn Error>> + Send + 'static,
+{
+    thread::spawn(move || {
+        match action() {
+            Ok(status) => set_status(status),
+            Err(err) => {
+                log::error!("Save manager: {}", err);
+                set_status(format!("Error: {}", err));
+            }
+        }
+        refresh();
+    });
+}
+
+/// How long ago a save was played, for the list
+pub(crate) fn format_age(last_played: u64) -> String {
+    if last_played == 0 {
+        return "unknown".to_string();
+    }
+    let seconds = save_file::now().saturating_sub(last_played);
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_file::{SAVE_LENGTH, SAVE_SLOTS};
+
+    /// An empty directory of its own for a test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "dmc1_save_manager_test_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A save with two backups, each holding a different fill byte (3 newest)
+    fn save_with_backups(dir: &Path, slot_name: &str) -> PathBuf {
+        let path = dir.join(format!("dmc1_seed_{}.sav", slot_name));
+        let header = SaveHeader::new("seed", slot_name, [0; SAVE_SLOTS]);
+        for fill in 1..=3 {
+            save_file::write_save(&path, &header, &[fill; SAVE_LENGTH]).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn import_checks_names() {
+        let dir = temp_dir("import");
+        let path = dir.join("exported.sav");
+        let header = SaveHeader::new("seed", "../Dante", [0; SAVE_SLOTS]);
+        save_file::write_save(&path, &header, &[1; SAVE_LENGTH]).unwrap();
+        assert!(import_save(&path).is_err());
+        assert!(!save_file::save_path("seed", "../Dante").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn delete_moves_backups_along() {
+        let dir = temp_dir("delete");
+        let path = save_with_backups(&dir, "Dante");
+        let deleted = dir.join("deleted");
+        move_to_folder(&path, &deleted).unwrap();
+        assert!(!path.exists());
+        assert!(save_file::existing_backups(&path).is_empty());
+        assert_eq!(list_files(&deleted).len(), 3);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rename_moves_backups_along() {
+        let dir = temp_dir("rename");
+        let entry = read_entry(save_with_backups(&dir, "Dante"));
+        let renamed = rename_save(&entry, "Vergil").unwrap();
+        assert_eq!(renamed, dir.join("dmc1_seed_Vergil.sav"));
+        assert!(!entry.path.exists());
+        assert!(save_file::existing_backups(&entry.path).is_empty());
+
+        let (data, header) = save_file::read_file(&renamed).unwrap();
+        assert_eq!(data[0], 3);
+        assert_eq!(header.unwrap().slot_name, "Vergil");
+        let backups = save_file::existing_backups(&renamed);
+        assert_eq!(backups.len(), 2);
+        for (backup, fill) in backups.iter().zip([2, 1]) {
+            let (data, header) = save_file::read_file(backup).unwrap();
+            assert_eq!(data[0], fill);
+            assert_eq!(header.unwrap().slot_name, "Vergil");
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rename_never_overwrites() {
+        let dir = temp_dir("overwrite");
+        let entry = read_entry(save_with_backups(&dir, "Dante"));
+        let other = save_with_backups(&dir, "Vergil");
+        assert!(rename_save(&entry, "Vergil").is_err());
+        assert!(entry.path.exists());
+        assert_eq!(save_file::existing_backups(&entry.path).len(), 2);
+        assert_eq!(
+            save_file::read_file(&other).unwrap().1.unwrap().slot_name,
+            "Vergil"
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rename_checks_the_name() {
+        let dir = temp_dir("rename_name");
+        let entry = read_entry(save_with_backups(&dir, "Dante"));
+        for name in ["", "../Vergil", "a/b", "what?"] {
+            assert!(rename_save(&entry, name).is_err(), "{:?}", name);
+        }
+        assert!(entry.path.exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::game_manager;
 use crate::game_manager::ArchipelagoData;
-use std::collections::HashMap;
+use crate::game_memory::GameMemory;
+use crate::mapping::MAPPING;
+use std::collections::{HashMap, HashSet};
 use std::ops::BitOrAssign;
 
-use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, RwLock};
 
 struct SkillData {
     id: usize,
@@ -135,10 +137,99 @@ static SKILLS_MAP: LazyLock<HashMap<&str, SkillData>> = LazyLock::new(|| {
         ),
     ])
 });
+/// Find the skill whose expertise flag is set in `new` but not in `old`
+pub(crate) fn find_new_skill(old: &[u8; 4], new: &[u8; 4]) -> Option<usize> {
+    SKILLS_MAP
+        .values()
+        .find(|data| old[data.index] & data.flag == 0 && new[data.index] & data.flag != 0)
+        .map(|data| data.id)
+}
+
+/// IDs of the skills bought from the shop. With randomized skills a purchase doesn't keep the skill, so the
+/// expertise flags can't tell the shop what has already been bought.
+static PURCHASED_SKILLS: LazyLock<RwLock<HashSet<usize>>> =
+    LazyLock::new(|| RwLock::new(HashSet::new()));
+
+/// Whether the expertise flags currently hold the purchased skills rather than the received ones
+static SHOWING_PURCHASES: AtomicBool = AtomicBool::new(false);
+
+/// Rebuild the purchased skills from the slot's checked locations
+pub(crate) fn set_purchased_skills<S: AsRef<str>>(checked_locations: &[S]) {
+    *PURCHASED_SKILLS.write().unwrap() = purchased_from_locations(checked_locations);
+}
+
+fn purchased_from_locations<S: AsRef<str>>(checked_locations: &[S]) -> HashSet<usize> {
+    checked_locations
+        .iter()
+        .filter_map(|loc| loc.as_ref().strip_prefix("Purchase "))
+        .filter_map(|name| SKILLS_MAP.get(name))
+        .map(|data| data.id)
+        .collect()
+}
+
+pub(crate) fn add_purchased_skill(id: usize) {
+    PURCHASED_SKILLS.write().unwrap().insert(id);
+}
+
+/// The expertise flags for the skills bought so far, this is what the shop needs to see to offer the right level
+pub(crate) fn purchased_expertise() -> [u8; 4] {
+    expertise_for(&PURCHASED_SKILLS.read().unwrap())
+}
+
+fn expertise_for(skill_ids: &HashSet<usize>) -> [u8; 4] {
+    let mut expertise = DEFAULT_SKILLS;
+    for data in skill_ids
+        .iter()
+        .filter_map(|id| ID_SKILL_MAP.get(id))
+        .filter_map(|name| SKILLS_MAP.get(name))
+    {
+        expertise[data.index].bitor_assign(data.flag);
+    }
+    expertise
+}
+
+fn skills_randomized() -> bool {
+    MAPPING
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|mapping| mapping.randomize_skills)
+}
+
+/// Run every frame. With randomized skills the shop is shown the purchased skills, everywhere else Dante has the
+/// skills he's received.
+pub(crate) fn update_shop_expertise<M: GameMemory>(
+    memory: &M,
+    in_shop: bool,
+    data: &ArchipelagoData,
+) {
+    if !skills_randomized() {
+        return;
+    }
+    if in_shop && !SHOWING_PURCHASES.swap(true, Ordering::SeqCst) {
+        if let Err(err) = memory.with_session(|s| s.expertise = purchased_expertise()) {
+            log::error!("Failed to set shop expertise: {:?}", err);
+        }
+    } else if !in_shop && SHOWING_PURCHASES.swap(false, Ordering::SeqCst) {
+        set_skills(memory, data);
+    }
+}
+
+/// Put back what the expertise flags should hold after a skill purchase, based on whether the shop is showing
+pub(crate) fn restore_after_purchase<M: GameMemory>(memory: &M, data: &ArchipelagoData) {
+    if SHOWING_PURCHASES.load(Ordering::SeqCst) {
+        if let Err(err) = memory.with_session(|s| s.expertise = purchased_expertise()) {
+            log::error!("Failed to set shop expertise: {:?}", err);
+        }
+    } else {
+        set_skills(memory, data);
+    }
+}
+
 static DEFAULT_SKILLS: [u8; 4] = [0x0, 0x0, 0x0, 0x0]; // I should see what else this lets me control...
 
-pub(crate) fn reset_expertise() {
-    match game_manager::with_session(|s| {
+pub(crate) fn reset_expertise<M: GameMemory>(memory: &M) {
+    match memory.with_session(|s| {
         s.expertise = DEFAULT_SKILLS;
     }) {
         Ok(_) => {}
@@ -148,20 +239,21 @@ pub(crate) fn reset_expertise() {
     }
 }
 
-fn give_skill(skill_name: &&'static str) {
+fn give_skill<M: GameMemory>(memory: &M, skill_name: &&'static str) {
     // This works, might not update files? need to double-check
     let data = SKILLS_MAP.get(skill_name).unwrap();
-    game_manager::with_session(|s| {
-        s.expertise[data.index].bitor_assign(data.flag);
-    })
-    .expect("Unable to give skill");
+    memory
+        .with_session(|s| {
+            s.expertise[data.index].bitor_assign(data.flag);
+        })
+        .expect("Unable to give skill");
 }
 
-pub(crate) fn set_skills(data: &ArchipelagoData) {
+pub(crate) fn set_skills<M: GameMemory>(memory: &M, data: &ArchipelagoData) {
     // I kinda don't like this tbh, but oh well, shouldn't really be an issue.
-    reset_expertise();
+    reset_expertise(memory);
     for skill in data.skills.iter() {
-        give_skill(skill);
+        give_skill(memory, skill);
     }
 }
 
@@ -183,7 +275,12 @@ pub(crate) fn add_skill(id: usize, data: &mut ArchipelagoData) {
         _ => {}
     }
 
-    let skill_name = match id {
+    data.add_skill(ID_SKILL_MAP.get(&leveled_skill_id(id, data)).unwrap());
+}
+
+/// Get the ID of the skill level that matches the current level of a levelled skill
+pub(crate) fn leveled_skill_id(id: usize, data: &ArchipelagoData) -> usize {
+    match id {
         100 => match data.stinger_level {
             1 => 100,
             2 => 101,
@@ -205,6 +302,38 @@ pub(crate) fn add_skill(id: usize, data: &mut ArchipelagoData) {
             _ => unreachable!(),
         },
         _ => id,
-    };
-    data.add_skill(ID_SKILL_MAP.get(&skill_name).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purchases_come_from_checked_skill_locations() {
+        let purchased = purchased_from_locations(&[
+            "Purchase Alastor - Stinger Level 1",
+            "Purchase Blue Orb #1",
+            "Mission #1 Complete",
+            "Purchase Ifrit - Inferno",
+        ]);
+        assert_eq!(purchased, HashSet::from([100, 113]));
+    }
+
+    #[test]
+    fn shop_sees_the_next_level_after_a_purchase() {
+        let stinger_1 = SKILLS_MAP.get("Alastor - Stinger Level 1").unwrap();
+        let stinger_2 = SKILLS_MAP.get("Alastor - Stinger Level 2").unwrap();
+        let before = expertise_for(&HashSet::from([100]));
+        assert_eq!(before[stinger_1.index] & stinger_1.flag, stinger_1.flag);
+        // The game buying level 2 sets its flag on top of what was bought before
+        let mut after = before;
+        after[stinger_2.index] |= stinger_2.flag;
+        assert_eq!(find_new_skill(&before, &after), Some(101));
+    }
+
+    #[test]
+    fn nothing_bought_gives_default_expertise() {
+        assert_eq!(expertise_for(&HashSet::new()), DEFAULT_SKILLS);
+    }
 }
@@ -0,0 +1,180 @@
+use crate::AP_CORE;
+use crate::ui::input::InputEvent;
+use crate::ui::overlay;
+use crate::ui::overlay::MessageSegment;
+use archipelago_rs::{Print, RichText};
+use randomizer_utilities::ui::font_handler::{GREEN, WHITE, YELLOW, draw_string};
+use randomizer_utilities::ui::overlay::D3D11State;
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    VK_BACK, VK_ESCAPE, VK_F9, VK_NEXT, VK_PRIOR, VK_RETURN,
+};
+
+/// How many messages are kept around, older ones are dropped
+const MAX_MESSAGES: usize = 200;
+const VISIBLE_LINES: usize = 12;
+const LINE_HEIGHT: f32 = 36.0;
+
+struct ChatLog {
+    messages: VecDeque<Vec<MessageSegment>>,
+    open: bool,
+    /// Lines scrolled up from the newest message
+    scroll: usize,
+    /// Text being typed, None when not typing
+    input: Option<String>,
+}
+
+static CHAT_LOG: LazyLock<Mutex<ChatLog>> = LazyLock::new(|| {
+    Mutex::new(ChatLog {
+        messages: VecDeque::new(),
+        open: false,
+        scroll: 0,
+        input: None,
+    })
+});
+
+pub(crate) fn add_entry(segments: Vec<MessageSegment>) {
+    match CHAT_LOG.lock() {
+        Ok(mut log) => {
+            log.messages.push_back(segments);
+            if log.messages.len() > MAX_MESSAGES {
+                log.messages.pop_front();
+            }
+            // Stay on the same messages if the player has scrolled up
+            if log.scroll > 0 {
+                log.scroll = (log.scroll + 1).min(log.messages.len().saturating_sub(1));
+            }
+        }
+        Err(err) => {
+            log::error!("Failed to add to chat log: {}", err);
+        }
+    }
+}
+
+/// Add a print from the server. Each part of it gets its own color, players are highlighted and items are colored by
+/// classification
+pub(crate) fn add_print(print: &Print) {
+    let segments = print
+        .data()
+        .iter()
+        .map(|part| match part {
+            RichText::Player(player) => MessageSegment::new(player.alias().to_string(), YELLOW),
+            RichText::Item(item) => MessageSegment::new(
+                item.item().name().to_string(),
+                overlay::get_color_for_item(item),
+            ),
+            RichText::Location(location) => MessageSegment::new(location.name().to_string(), GREEN),
+            other => MessageSegment::new(other.to_string(), WHITE),
+        })
+        .collect();
+    add_entry(segments);
+}
+
+/// Add a line of plain text that didn't come from the server
+pub(crate) fn add_notice(text: String) {
+    add_entry(vec![MessageSegment::new(text, WHITE)]);
+}
+
+/// F9 opens and closes the log, Page Up/Down scroll it and Enter starts typing a message.
+/// Anything starting with '!' (like !hint) is a server command and goes out the same way.
+pub(crate) fn handle_input(event: InputEvent) -> bool {
+    let Ok(mut log) = CHAT_LOG.lock() else {
+        return false;
+    };
+    if event == InputEvent::Key(VK_F9) {
+        log.open = !log.open;
+        log.input = None;
+        log.scroll = 0;
+        return true;
+    }
+    if !log.open {
+        return false;
+    }
+    let max_scroll = log.messages.len().saturating_sub(VISIBLE_LINES);
+    match event {
+        InputEvent::Key(VK_PRIOR) => log.scroll = (log.scroll + VISIBLE_LINES).min(max_scroll),
+        InputEvent::Key(VK_NEXT) => log.scroll = log.scroll.saturating_sub(VISIBLE_LINES),
+        InputEvent::Key(VK_RETURN) => match log.input.take() {
+            Some(text) if !text.trim().is_empty() => send_message(text),
+            Some(_) => {}
+            None => log.input = Some(String::new()),
+        },
+        InputEvent::Key(VK_ESCAPE) => {
+            if log.input.take().is_none() {
+                log.open = false;
+            }
+        }
+        InputEvent::Key(VK_BACK) => {
+            let Some(input) = log.input.as_mut() else {
+                return false;
+            };
+            input.pop();
+        }
+        InputEvent::Char(c) => {
+            let Some(input) = log.input.as_mut() else {
+                return false;
+            };
+            input.push(c);
+        }
+        // Leave everything else to the game unless a message is being typed
+        _ => return log.input.is_some(),
+    }
+    true
+}
+
+fn send_message(text: String) {
+    thread::spawn(move || match AP_CORE.get().map(|core| core.lock()) {
+        Some(Ok(mut core)) => match core.client_mut() {
+            Some(client) => {
+                if let Err(err) = client.say(text) {
+                    log::error!("Failed to send message: {}", err);
+                }
+            }
+            None => add_notice("Not connected, message was not sent".to_string()),
+        },
+        Some(Err(err)) => log::error!("Failed to get Archipelago core: {}", err),
+        None => log::error!("Archipelago core hasn't been set up yet"),
+    });
+}
+
+pub(crate) fn draw(state: &D3D11State, screen_width: f32, screen_height: f32) {
+    let Ok(log) = CHAT_LOG.lock() else {
+        return;
+    };
+    if !log.open {
+        return;
+    }
+    let Some(atlas) = &state.atlas else {
+        return;
+    };
+    let end = log.messages.len() - log.scroll.min(log.messages.len());
+    let start = end.saturating_sub(VISIBLE_LINES);
+    let mut y = screen_height * 0.4;
+    for segments in log.messages.range(start..end) {
+        let mut x = 0.0;
+        for segment in segments {
+            draw_string(
+                state,
+                &segment.text,
+                x,
+                y,
+                screen_width,
+                screen_height,
+                &segment.color,
+            );
+            x += segment
+                .text
+                .chars()
+                .map(|c| atlas.glyph_advance(c))
+                .sum::<f32>();
+        }
+        y += LINE_HEIGHT;
+    }
+    let prompt = match &log.input {
+        Some(input) => format!("> {}_", input),
+        None => "Enter: Chat  PgUp/PgDn: Scroll  F9: Close".to_string(),
+    };
+    draw_string(state, &prompt, 0.0, y, screen_width, screen_height, &YELLOW);
+}
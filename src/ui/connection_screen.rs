@@ -0,0 +1,229 @@
+use crate::AP_CORE;
+use crate::archipelago::CONNECTED;
+use crate::config;
+use crate::config::ServerDetails;
+use crate::ui::input::{InputEvent, PadButton};
+use crate::{mission_select, utilities};
+use randomizer_utilities::ui::font_handler::{WHITE, YELLOW, draw_string};
+use randomizer_utilities::ui::overlay::D3D11State;
+use std::sync::atomic::Ordering;
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    VK_BACK, VK_DOWN, VK_ESCAPE, VK_F10, VK_RETURN, VK_TAB, VK_UP,
+};
+
+const LINE_HEIGHT: f32 = 50.0;
+/// Characters a controller can cycle through, there's no on-screen keyboard
+const PAD_CHARSET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.:/-_ ";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Field {
+    Address,
+    Slot,
+    Password,
+    Connect,
+}
+
+const FIELDS: [Field; 4] = [Field::Address, Field::Slot, Field::Password, Field::Connect];
+
+impl Field {
+    fn next(self) -> Field {
+        match self {
+            Field::Address => Field::Slot,
+            Field::Slot => Field::Password,
+            Field::Password => Field::Connect,
+            Field::Connect => Field::Address,
+        }
+    }
+
+    fn previous(self) -> Field {
+        match self {
+            Field::Address => Field::Connect,
+            Field::Slot => Field::Address,
+            Field::Password => Field::Slot,
+            Field::Connect => Field::Password,
+        }
+    }
+}
+
+struct ConnectionScreen {
+    open: bool,
+    selected: Field,
+    details: ServerDetails,
+}
+
+impl ConnectionScreen {
+    fn selected_text(&mut self) -> Option<&mut String> {
+        match self.selected {
+            Field::Address => Some(&mut self.details.url),
+            Field::Slot => Some(&mut self.details.slot_name),
+            Field::Password => Some(&mut self.details.password),
+            Field::Connect => None,
+        }
+    }
+}
+
+static SCREEN: LazyLock<Mutex<ConnectionScreen>> = LazyLock::new(|| {
+    Mutex::new(ConnectionScreen {
+        open: false,
+        selected: Field::Address,
+        details: config::get_last_server_details(),
+    })
+});
+
+/// True while the screen is up and using the controller
+pub(crate) fn is_open() -> bool {
+    utilities::is_on_main_menu() && SCREEN.lock().is_ok_and(|screen| screen.open)
+}
+
+/// Only takes input on the main menu. F10/Back opens and closes the screen, while it's open it
+/// keeps all keyboard and controller input to itself.
+pub(crate) fn handle_input(event: InputEvent) -> bool {
+    if !utilities::is_on_main_menu() {
+        return false;
+    }
+    let Ok(mut screen) = SCREEN.lock() else {
+        return false;
+    };
+    if matches!(
+        event,
+        InputEvent::Key(VK_F10) | InputEvent::Pad(PadButton::Back)
+    ) {
+        screen.open = !screen.open;
+        return true;
+    }
+    if !screen.open {
+        return false;
+    }
+    match event {
+        InputEvent::Key(VK_ESCAPE) | InputEvent::Pad(PadButton::B) => screen.open = false,
+        InputEvent::Key(VK_UP) | InputEvent::Pad(PadButton::Up) => {
+            screen.selected = screen.selected.previous()
+        }
+        InputEvent::Key(VK_DOWN) | InputEvent::Key(VK_TAB) | InputEvent::Pad(PadButton::Down) => {
+            screen.selected = screen.selected.next()
+        }
+        InputEvent::Key(VK_RETURN) | InputEvent::Pad(PadButton::A) => {
+            if screen.selected == Field::Connect {
+                toggle_connection(screen.details.clone());
+            } else {
+                screen.selected = screen.selected.next()
+            }
+        }
+        InputEvent::Key(VK_BACK) | InputEvent::Pad(PadButton::X) => {
+            if let Some(text) = screen.selected_text() {
+                text.pop();
+            }
+        }
+        InputEvent::Char(c) => {
+            if let Some(text) = screen.selected_text() {
+                text.push(c);
+            }
+        }
+        InputEvent::Pad(PadButton::Y) => {
+            if let Some(text) = screen.selected_text() {
+                text.push('a');
+            }
+        }
+        InputEvent::Pad(PadButton::Left) | InputEvent::Pad(PadButton::Right) => {
+            let forward = event == InputEvent::Pad(PadButton::Right);
+            if let Some(text) = screen.selected_text()
+                && let Some(last) = text.pop()
+            {
+                text.push(cycle_char(last, forward));
+            }
+        }
+        _ => {}
+    }
+    true
+}
+
+fn cycle_char(current: char, forward: bool) -> char {
+    let chars: Vec<char> = PAD_CHARSET.chars().collect();
+    let idx = chars.iter().position(|c| *c == current).unwrap_or(0);
+    let next = if forward {
+        (idx + 1) % chars.len()
+    } else {
+        (idx + chars.len() - 1) % chars.len()
+    };
+    chars[next]
+}
+
+fn toggle_connection(details: ServerDetails) {
+    thread::spawn(move || match AP_CORE.get().map(|core| core.lock()) {
+        Some(Ok(mut core)) => {
+            if CONNECTED.load(Ordering::SeqCst) {
+                core.disconnect();
+            } else {
+                core.connect(details);
+            }
+        }
+        Some(Err(err)) => log::error!("Failed to get Archipelago core: {}", err),
+        None => log::error!("Archipelago core hasn't been set up yet"),
+    });
+}
+
+pub(crate) fn draw(state: &D3D11State, screen_width: f32, screen_height: f32) {
+    let Ok(screen) = SCREEN.lock() else {
+        return;
+    };
+    let x = screen_width * 0.55;
+    let mut y = screen_height - LINE_HEIGHT * (FIELDS.len() + 1) as f32;
+    if !screen.open {
+        draw_string(
+            state,
+            "F10/Back: Connection settings",
+            x,
+            screen_height - LINE_HEIGHT,
+            screen_width,
+            screen_height,
+            &WHITE,
+        );
+        if mission_select::is_free_select() {
+            draw_string(
+                state,
+                &match mission_select::get_selected_mission() {
+                    Some(mission) => format!("F6/F7: Mission #{}", mission),
+                    None => "F6/F7: Select mission".to_string(),
+                },
+                x,
+                screen_height - LINE_HEIGHT * 2.0,
+                screen_width,
+                screen_height,
+                &WHITE,
+            );
+        }
+        return;
+    }
+    for field in FIELDS {
+        let selected = field == screen.selected;
+        let cursor = if selected { "_" } else { "" };
+        let text = match field {
+            Field::Address => format!("Address: {}{}", screen.details.url, cursor),
+            Field::Slot => format!("Slot: {}{}", screen.details.slot_name, cursor),
+            Field::Password => format!(
+                "Password: {}{}",
+                "*".repeat(screen.details.password.chars().count()),
+                cursor
+            ),
+            Field::Connect => {
+                if CONNECTED.load(Ordering::SeqCst) {
+                    "[Disconnect]".to_string()
+                } else {
+                    "[Connect]".to_string()
+                }
+            }
+        };
+        draw_string(
+            state,
+            &text,
+            x,
+            y,
+            screen_width,
+            screen_height,
+            if selected { &YELLOW } else { &WHITE },
+        );
+        y += LINE_HEIGHT;
+    }
+}
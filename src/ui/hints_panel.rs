@@ -0,0 +1,118 @@
+use crate::ui::input::InputEvent;
+use randomizer_utilities::ui::font_handler::{GREEN, WHITE, YELLOW, draw_string};
+use randomizer_utilities::ui::overlay::D3D11State;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{LazyLock, RwLock};
+use windows::Win32::UI::Input::KeyboardAndMouse::{VK_F8, VK_NEXT, VK_PRIOR};
+
+const VISIBLE_HINTS: usize = 12;
+const LINE_HEIGHT: f32 = 36.0;
+
+#[derive(Debug, Clone)]
+pub(crate) struct HintEntry {
+    pub(crate) location: String,
+    pub(crate) item: String,
+    /// Whose world the item is in
+    pub(crate) finder: String,
+    /// Who the item is for
+    pub(crate) receiver: String,
+    pub(crate) found: bool,
+}
+
+static HINTS: LazyLock<RwLock<Vec<HintEntry>>> = LazyLock::new(|| RwLock::new(vec![]));
+static OPEN: AtomicBool = AtomicBool::new(false);
+/// Index of the first hint shown
+static SCROLL: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace the hint list, unfound hints go first since they're the useful ones
+pub(crate) fn set_hints(mut hints: Vec<HintEntry>) {
+    hints.sort_by_key(|hint| hint.found);
+    match HINTS.write() {
+        Ok(mut current) => {
+            *current = hints;
+            SCROLL.fetch_min(max_scroll(current.len()), Ordering::SeqCst);
+        }
+        Err(err) => log::error!("Failed to update hints: {}", err),
+    }
+}
+
+fn max_scroll(hints: usize) -> usize {
+    hints.saturating_sub(VISIBLE_HINTS)
+}
+
+/// F8 opens and closes the panel, Page Up/Down scroll it while it's open
+pub(crate) fn handle_input(event: InputEvent) -> bool {
+    if event == InputEvent::Key(VK_F8) {
+        OPEN.fetch_xor(true, Ordering::SeqCst);
+        SCROLL.store(0, Ordering::SeqCst);
+        return true;
+    }
+    if !OPEN.load(Ordering::SeqCst) {
+        return false;
+    }
+    let max = HINTS
+        .read()
+        .map(|hints| max_scroll(hints.len()))
+        .unwrap_or(0);
+    let scroll = SCROLL.load(Ordering::SeqCst);
+    match event {
+        InputEvent::Key(VK_PRIOR) => {
+            SCROLL.store(scroll.saturating_sub(VISIBLE_HINTS), Ordering::SeqCst)
+        }
+        InputEvent::Key(VK_NEXT) => {
+            SCROLL.store((scroll + VISIBLE_HINTS).min(max), Ordering::SeqCst)
+        }
+        _ => return false,
+    }
+    true
+}
+
+pub(crate) fn draw(state: &D3D11State, screen_width: f32, screen_height: f32) {
+    if !OPEN.load(Ordering::SeqCst) {
+        return;
+    }
+    let Ok(hints) = HINTS.read() else {
+        return;
+    };
+    let x = screen_width * 0.45;
+    let mut y = screen_height * 0.3;
+    let scroll = SCROLL.load(Ordering::SeqCst).min(max_scroll(hints.len()));
+    let shown = hints.len().min(scroll + VISIBLE_HINTS);
+    draw_string(
+        state,
+        &if hints.len() > VISIBLE_HINTS {
+            format!(
+                "Hints ({}-{} of {}, Page Up/Down to scroll)",
+                scroll + 1,
+                shown,
+                hints.len()
+            )
+        } else {
+            format!("Hints ({})", hints.len())
+        },
+        x,
+        y,
+        screen_width,
+        screen_height,
+        &WHITE,
+    );
+    for hint in hints.iter().skip(scroll).take(VISIBLE_HINTS) {
+        y += LINE_HEIGHT;
+        draw_string(
+            state,
+            &format!(
+                "{} {}'s {} is at {} in {}'s world",
+                if hint.found { "[Found]" } else { "[Hint]" },
+                hint.receiver,
+                hint.item,
+                hint.location,
+                hint.finder
+            ),
+            x,
+            y,
+            screen_width,
+            screen_height,
+            if hint.found { &GREEN } else { &YELLOW },
+        );
+    }
+}
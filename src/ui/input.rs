@@ -0,0 +1,181 @@
+use crate::ui::{chat_log, connection_screen, hints_panel};
+use crate::{game_manager, mission_select};
+use minhook::MinHook;
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU16, Ordering};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::{GetModuleHandleA, GetProcAddress};
+use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
+use windows::Win32::UI::Input::XboxController::{
+    XINPUT_GAMEPAD, XINPUT_GAMEPAD_A, XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_BACK,
+    XINPUT_GAMEPAD_BUTTON_FLAGS, XINPUT_GAMEPAD_DPAD_DOWN, XINPUT_GAMEPAD_DPAD_LEFT,
+    XINPUT_GAMEPAD_DPAD_RIGHT, XINPUT_GAMEPAD_DPAD_UP, XINPUT_GAMEPAD_X, XINPUT_GAMEPAD_Y,
+    XINPUT_STATE, XInputGetState,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallWindowProcW, GWLP_WNDPROC, SetWindowLongPtrW, WM_CHAR, WM_KEYDOWN, WNDPROC,
+};
+use windows::core::{PCSTR, s};
+
+/// Input the overlay's own screens can react to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum InputEvent {
+    Char(char),
+    Key(VIRTUAL_KEY),
+    Pad(PadButton),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum PadButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    X,
+    Y,
+    Back,
+}
+
+const PAD_BUTTONS: [(XINPUT_GAMEPAD_BUTTON_FLAGS, PadButton); 9] = [
+    (XINPUT_GAMEPAD_DPAD_UP, PadButton::Up),
+    (XINPUT_GAMEPAD_DPAD_DOWN, PadButton::Down),
+    (XINPUT_GAMEPAD_DPAD_LEFT, PadButton::Left),
+    (XINPUT_GAMEPAD_DPAD_RIGHT, PadButton::Right),
+    (XINPUT_GAMEPAD_A, PadButton::A),
+    (XINPUT_GAMEPAD_B, PadButton::B),
+    (XINPUT_GAMEPAD_X, PadButton::X),
+    (XINPUT_GAMEPAD_Y, PadButton::Y),
+    (XINPUT_GAMEPAD_BACK, PadButton::Back),
+];
+
+static ORIGINAL_WNDPROC: OnceLock<WNDPROC> = OnceLock::new();
+static PREVIOUS_BUTTONS: AtomicU16 = AtomicU16::new(0);
+
+type XInputGetStateFn = unsafe extern "system" fn(u32, *mut XINPUT_STATE) -> u32;
+static ORIGINAL_XINPUT_GET_STATE: OnceLock<XInputGetStateFn> = OnceLock::new();
+/// XInput versions the game could be using. 1.4 goes last since the mod itself always loads it.
+const XINPUT_DLLS: [PCSTR; 3] = [
+    s!("xinput1_3.dll"),
+    s!("xinput9_1_0.dll"),
+    s!("xinput1_4.dll"),
+];
+
+thread_local! {
+    /// Set while the overlay reads the controller itself, so its reads aren't blanked out
+    static OVERLAY_POLLING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Hands the event to whichever overlay screen wants it. True if it was used
+fn dispatch(event: InputEvent) -> bool {
+    connection_screen::handle_input(event)
+        || chat_log::handle_input(event)
+        || hints_panel::handle_input(event)
+        || mission_select::handle_input(event)
+}
+
+/// Subclass the game window so typed text reaches the overlay
+pub(crate) fn install_wndproc(hwnd: HWND) {
+    if ORIGINAL_WNDPROC.get().is_some() {
+        return;
+    }
+    unsafe {
+        let original = SetWindowLongPtrW(hwnd, GWLP_WNDPROC, wndproc_hook as usize as isize);
+        if original == 0 {
+            log::error!("Failed to install window procedure hook");
+            return;
+        }
+        if ORIGINAL_WNDPROC.set(std::mem::transmute(original)).is_err() {
+            log::error!("Window procedure hook already set");
+        }
+    }
+    log::debug!("Installed window procedure hook");
+}
+
+unsafe extern "system" fn wndproc_hook(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    let event = match msg {
+        WM_CHAR => char::from_u32(wparam.0 as u32)
+            .filter(|c| !c.is_control())
+            .map(InputEvent::Char),
+        WM_KEYDOWN => Some(InputEvent::Key(VIRTUAL_KEY(wparam.0 as u16))),
+        _ => None,
+    };
+    if let Some(event) = event
+        && dispatch(event)
+    {
+        return LRESULT(0);
+    }
+    unsafe { CallWindowProcW(*ORIGINAL_WNDPROC.get().unwrap(), hwnd, msg, wparam, lparam) }
+}
+
+/// Hook XInputGetState so the game doesn't see the controller while an overlay screen is using it, or while an
+/// input lock trap is going
+pub(crate) fn install_xinput_hook() {
+    if ORIGINAL_XINPUT_GET_STATE.get().is_some() {
+        return;
+    }
+    for dll in XINPUT_DLLS {
+        let Ok(module) = (unsafe { GetModuleHandleA(dll) }) else {
+            continue;
+        };
+        let Some(target) = (unsafe { GetProcAddress(module, s!("XInputGetState")) }) else {
+            continue;
+        };
+        let target = target as *mut c_void;
+        let result = unsafe {
+            MinHook::create_hook(target, xinput_get_state_hook as *mut c_void).and_then(
+                |original| {
+                    let _ = ORIGINAL_XINPUT_GET_STATE.set(std::mem::transmute(original));
+                    MinHook::enable_hook(target)
+                },
+            )
+        };
+        match result {
+            Ok(_) => log::debug!("Installed XInput hook"),
+            Err(err) => log::error!("Failed to install XInput hook: {:?}", err),
+        }
+        return;
+    }
+    log::warn!("XInput isn't loaded, the game will still see controller input used by the overlay");
+}
+
+unsafe extern "system" fn xinput_get_state_hook(user_index: u32, state: *mut XINPUT_STATE) -> u32 {
+    let result = unsafe { ORIGINAL_XINPUT_GET_STATE.get().unwrap()(user_index, state) };
+    if result == 0
+        && !state.is_null()
+        && !OVERLAY_POLLING.get()
+        && (connection_screen::is_open() || game_manager::is_input_locked())
+    {
+        // Nothing's pressed as far as the game is concerned
+        unsafe { (*state).Gamepad = XINPUT_GAMEPAD::default() };
+    }
+    result
+}
+
+/// Read the first controller and send out any buttons that were just pressed. Run once per frame.
+/// The game doesn't see the controller while a screen that uses it is open.
+pub(crate) fn poll_controller() {
+    let mut state = XINPUT_STATE::default();
+    OVERLAY_POLLING.set(true);
+    let result = unsafe { XInputGetState(0, &mut state) };
+    OVERLAY_POLLING.set(false);
+    if result != 0 {
+        return;
+    }
+    let buttons = state.Gamepad.wButtons.0;
+    let previous = PREVIOUS_BUTTONS.swap(buttons, Ordering::SeqCst);
+    let pressed = buttons & !previous;
+    for (flag, button) in PAD_BUTTONS {
+        if pressed & flag.0 != 0 {
+            dispatch(InputEvent::Pad(button));
+        }
+    }
+}
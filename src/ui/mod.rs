@@ -1,3 +1,7 @@
+pub(crate) mod chat_log;
+pub(crate) mod connection_screen;
 pub(crate) mod dx11_hooks;
+pub(crate) mod hints_panel;
+pub(crate) mod input;
 pub(crate) mod overlay;
 pub(crate) mod text_handler;
@@ -1,11 +1,13 @@
 use crate::archipelago::CONNECTED;
-use crate::{mapping, utilities};
+use crate::ui::{chat_log, connection_screen, hints_panel, input};
+use crate::{check_handler, mapping, utilities};
 use archipelago_rs::LocatedItem;
+use randomizer_utilities::archipelago_utilities::{CACHED_LOCATIONS, get_description};
 use randomizer_utilities::dmc::loader_parser::LOADER_STATUS;
 use randomizer_utilities::ui::dx11::{ORIGINAL_PRESENT, ORIGINAL_RESIZE_BUFFERS};
 use randomizer_utilities::ui::dx11_state_guard;
 use randomizer_utilities::ui::font_handler::{
-    FontAtlas, FontColorCB, GREEN, RED, WHITE, draw_string,
+    FontAtlas, FontColorCB, GREEN, RED, WHITE, YELLOW, draw_string,
 };
 use randomizer_utilities::ui::overlay::{D3D11State, STATE, get_resources};
 use std::collections::VecDeque;
@@ -75,6 +77,28 @@ pub(crate) fn add_message(overlay: OverlayMessage) {
     }
 }
 
+/// Remember the upcoming mission for the main menu and let the player know about it
+pub(crate) fn set_next_mission(mission: u8) {
+    match mapping::OVERLAY_INFO.write() {
+        Ok(mut info) => {
+            info.next_mission = Some(mission);
+        }
+        Err(err) => {
+            log::error!("Failed to set next mission: {}", err);
+        }
+    }
+    add_message(OverlayMessage::new(
+        vec![
+            MessageSegment::new("Next Mission: ".to_string(), WHITE),
+            MessageSegment::new(format!("#{}", mission), GREEN),
+        ],
+        Duration::from_secs(5),
+        0.0,
+        0.0,
+        MessageType::Notification,
+    ));
+}
+
 pub(crate) unsafe extern "system" fn resize_hook(
     swap_chain: *mut IDXGISwapChain,
     buffer_count: u32,
@@ -171,21 +195,35 @@ fn draw_overlay(screen_width: f32, screen_height: f32, state: &RwLockReadGuard<D
             screen_height,
             get_default_color(),
         );
-        let connected = CONNECTED.load(Ordering::SeqCst);
+        let (status, color) = get_connection_status();
         draw_string(
             state,
-            if connected {
-                "Connected"
-            } else {
-                "Disconnected"
-            },
+            &status,
             STATUS.chars().map(|c| atlas.glyph_advance(c)).sum::<f32>(),
             0.0,
             screen_width,
             screen_height,
-            &if connected { GREEN } else { RED },
+            &color,
         );
-        draw_version_info(state, screen_width, screen_height, atlas);
+        if CONNECTED.load(Ordering::SeqCst)
+            && let Ok(info) = mapping::OVERLAY_INFO.read()
+            && let Some(next) = info.next_mission
+        {
+            draw_string(
+                state,
+                &format!("Next Mission: #{}", next),
+                0.0,
+                50.0,
+                screen_width,
+                screen_height,
+                get_default_color(),
+            );
+        }
+        if utilities::is_on_main_menu() {
+            draw_version_info(state, screen_width, screen_height, atlas);
+            input::poll_controller();
+            connection_screen::draw(state, screen_width, screen_height);
+        }
     }
     if CANT_PURCHASE.load(Ordering::SeqCst)
         && let Some(atlas) = &state.atlas
@@ -223,7 +261,15 @@ fn draw_overlay(screen_width: f32, screen_height: f32, state: &RwLockReadGuard<D
         );
         CANT_PURCHASE.store(false, Ordering::SeqCst);
     }
+    if CONNECTED.load(Ordering::SeqCst)
+        && utilities::is_in_shop()
+        && let Some(atlas) = &state.atlas
+    {
+        draw_shop_preview(state, screen_width, screen_height, atlas);
+    }
 
+    chat_log::draw(state, screen_width, screen_height);
+    hints_panel::draw(state, screen_width, screen_height);
     pop_buffer_message();
 
     let now = Instant::now();
@@ -343,15 +389,70 @@ fn draw_version_info(
     }
 }
 
+/// Shows what the highlighted store item will actually give before it's bought
+fn draw_shop_preview(
+    state: &RwLockReadGuard<D3D11State>,
+    screen_width: f32,
+    screen_height: f32,
+    atlas: &FontAtlas,
+) {
+    const PREVIEW: &str = "Purchasing gives: ";
+    if let Some(location_key) = check_handler::get_shop_preview()
+        && let Ok(cached_locations) = CACHED_LOCATIONS.read()
+        && let Some(item) = cached_locations.get(location_key)
+    {
+        draw_string(
+            state,
+            PREVIEW,
+            480.0,
+            142.0,
+            screen_width,
+            screen_height,
+            &WHITE,
+        );
+        draw_string(
+            state,
+            &get_description(item),
+            480.0 + PREVIEW.chars().map(|c| atlas.glyph_advance(c)).sum::<f32>(),
+            142.0,
+            screen_width,
+            screen_height,
+            &get_color_for_item(item),
+        );
+    }
+}
+
 fn get_default_color() -> &'static FontColorCB {
     &WHITE
 }
 
-fn should_display_anyway() -> bool {
-    // TODO Use this to display if we are connected, then disconnected
-    // Or if version mismatch?
+fn get_connection_status() -> (String, FontColorCB) {
+    if CONNECTED.load(Ordering::SeqCst) {
+        return ("Connected".to_string(), GREEN);
+    }
+    match mapping::OVERLAY_INFO
+        .read()
+        .ok()
+        .and_then(|info| info.reconnect_at)
+    {
+        Some(at) => (
+            format!(
+                "Reconnecting in {}s",
+                at.saturating_duration_since(Instant::now()).as_secs() + 1
+            ),
+            YELLOW,
+        ),
+        None => ("Disconnected".to_string(), RED),
+    }
+}
 
-    false
+fn should_display_anyway() -> bool {
+    // TODO Version mismatch?
+    // Keep the status up while gameplay is running without a connection
+    !CONNECTED.load(Ordering::SeqCst)
+        && mapping::OVERLAY_INFO
+            .read()
+            .is_ok_and(|info| info.reconnect_at.is_some())
 }
 
 fn draw_colored_message(
@@ -425,12 +526,16 @@ fn pop_buffer_message() {
 }
 
 pub(crate) fn get_color_for_item(item: &LocatedItem) -> FontColorCB {
+    get_color_for_flags(item.is_trap(), item.is_useful(), item.is_progression())
+}
+
+pub(crate) fn get_color_for_flags(trap: bool, useful: bool, progression: bool) -> FontColorCB {
     const CYAN: FontColorCB = FontColorCB::new(0.0, 0.933, 0.933, 1.0);
     const PLUM: FontColorCB = FontColorCB::new(0.686, 0.6, 0.937, 1.0);
     const STATE_BLUE: FontColorCB = FontColorCB::new(0.427, 0.545, 0.91, 1.0);
     const SALMON: FontColorCB = FontColorCB::new(0.98, 0.502, 0.447, 1.0);
 
-    match (item.is_trap(), item.is_useful(), item.is_progression()) {
+    match (trap, useful, progression) {
         (true, _, _) => SALMON,
         (false, _, true) => PLUM,
         (false, true, false) => STATE_BLUE,
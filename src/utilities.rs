@@ -9,59 +9,33 @@ pub fn is_ddmk_loaded() -> bool {
 }
 
 pub fn insert_unique_item_into_inv(item_data: &ItemData) {
-    with_session(|s| {
-        for i in 0..s.item_count {
-            let item = &mut s.item_data[i as usize];
-            if item == item_data {
-                item.count = item_data.count;
-                return;
-            }
-        }
-        let item = &mut s.item_data[s.item_count as usize];
-        item.category = item_data.category;
-        item.id = item_data.id;
-        item.count = item_data.count;
-        s.item_count += 1;
-    })
-    .unwrap();
+    if !with_session(|s| s.insert_unique_item(item_data)).unwrap() {
+        log::error!("Inventory is full, unable to add {:?}", item_data);
+    }
 }
 
 pub fn insert_item_into_inv(item_data: &ItemData) {
-    with_session(|s| {
-        for i in 0..s.item_count {
-            let item = &mut s.item_data[i as usize];
-            if item == item_data {
-                item.count += item_data.count;
-                return;
-            }
-        }
-        let item = &mut s.item_data[s.item_count as usize];
-        item.category = item_data.category;
-        item.id = item_data.id;
-        item.count = item_data.count;
-        s.item_count += 1;
-    })
-    .unwrap();
+    if !with_session(|s| s.insert_item(item_data)).unwrap() {
+        log::error!("Inventory is full, unable to add {:?}", item_data);
+    }
 }
 
 pub(crate) fn clear_item_slot(item_data: &ItemData) {
-    with_session(|s| {
-        for i in 0..s.item_count {
-            let item = &mut s.item_data[i as usize];
-            if item == item_data {
-                // Swap in last element
-                s.item_data[i as usize] = s.item_data[(s.item_count - 1) as usize];
-                s.item_count -= 1;
-                return;
-            }
-        }
-    })
-    .unwrap();
+    with_session(|s| s.clear_item(item_data)).unwrap();
+}
+
+fn get_current_screen() -> u8 {
+    read_data_from_address::<u8>(read_data_from_address::<usize>(*DMC1_ADDRESS + 0x60b018))
 }
 
 pub(crate) fn is_on_main_menu() -> bool {
     // Somehow works better than DMC3's
-    let val =
-        read_data_from_address::<u8>(read_data_from_address::<usize>(*DMC1_ADDRESS + 0x60b018));
-    val == 5
+    get_current_screen() == 5
+}
+
+/// Screen the Divinity Statue shop runs on
+const SHOP_SCREEN: u8 = 9;
+
+pub(crate) fn is_in_shop() -> bool {
+    get_current_screen() == SHOP_SCREEN
 }